@@ -0,0 +1,139 @@
+use std::{fmt, path::Path};
+
+use crate::{graph::json_string, Garden};
+
+/// Pages that are cut off from the rest of the garden, leaving out the entry
+/// points which are where readers come in anyway. Paths are relative to the
+/// root and sorted.
+pub struct Report<'a> {
+    /// Pages no other page links to
+    pub orphans: Vec<&'a Path>,
+    /// Pages that don't link to any other page
+    pub dead_ends: Vec<&'a Path>,
+}
+
+impl<'a> Report<'a> {
+    /// Entry points are page names, like `home`, or paths from the root
+    /// without the `.md`, like `projects/index`.
+    pub fn new<S: AsRef<str>>(garden: &'a Garden, entry_points: &[S]) -> Self {
+        let is_entry = |file: &Path| {
+            let relative = file.strip_prefix(garden.root).unwrap_or(file);
+            let without_ext = relative.with_extension("");
+
+            entry_points.iter().any(|entry| {
+                let entry = entry.as_ref();
+                without_ext == Path::new(entry)
+                    || relative.file_stem().map(|stem| stem == entry) == Some(true)
+            })
+        };
+
+        let mut orphans = vec![];
+        let mut dead_ends = vec![];
+        for file in garden.files {
+            if is_entry(file) {
+                continue;
+            }
+            let relative = file.strip_prefix(garden.root).unwrap_or(file);
+
+            if !garden.links.iter().any(|link| link.to == *file) {
+                orphans.push(relative);
+            }
+
+            if !garden.links.iter().any(|link| link.from == *file) {
+                dead_ends.push(relative);
+            }
+        }
+
+        orphans.sort();
+        dead_ends.sort();
+
+        Self { orphans, dead_ends }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orphans.is_empty() && self.dead_ends.is_empty()
+    }
+
+    /// `{"orphans": [path], "dead_ends": [path]}`
+    pub fn json(&self) -> String {
+        let list = |paths: &[&Path]| -> String {
+            paths
+                .iter()
+                .map(|path| json_string(&path.to_string_lossy()))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        format!(
+            "{{\"orphans\":[{}],\"dead_ends\":[{}]}}\n",
+            list(&self.orphans),
+            list(&self.dead_ends)
+        )
+    }
+}
+
+impl<'a> fmt::Display for Report<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Orphans, which nothing links to:")?;
+        for orphan in &self.orphans {
+            writeln!(f, "\t{}", orphan.display())?;
+        }
+
+        writeln!(f, "Dead ends, which link nowhere:")?;
+        for dead_end in &self.dead_ends {
+            writeln!(f, "\t{}", dead_end.display())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Link;
+    use std::{collections::HashMap, path::PathBuf};
+
+    #[test]
+    fn orphans_and_dead_ends() {
+        let files = [
+            Path::new("/wiki/home.md"),
+            Path::new("/wiki/notes.md"),
+            Path::new("/wiki/lost.md"),
+            Path::new("/wiki/inner/home.md"),
+            Path::new("/wiki/leaf.md"),
+        ];
+        let link = |from: &str, to: &str| Link {
+            from: PathBuf::from(from),
+            to: PathBuf::from(to),
+            excerpt: String::new(),
+        };
+
+        let garden = Garden {
+            root: Path::new("/wiki"),
+            files: &files,
+            headings: HashMap::new(),
+            titles: HashMap::new(),
+            links: vec![
+                link("/wiki/home.md", "/wiki/notes.md"),
+                link("/wiki/notes.md", "/wiki/home.md"),
+                link("/wiki/lost.md", "/wiki/notes.md"),
+                link("/wiki/notes.md", "/wiki/leaf.md"),
+            ],
+            external_links: vec![],
+        };
+
+        // Both homes are entry points, so the one that links nowhere is fine
+        let report = Report::new(&garden, &["home"]);
+        assert_eq!(report.orphans, vec![Path::new("lost.md")]);
+        assert_eq!(report.dead_ends, vec![Path::new("leaf.md")]);
+        assert_eq!(
+            report.json(),
+            "{\"orphans\":[\"lost.md\"],\"dead_ends\":[\"leaf.md\"]}\n"
+        );
+
+        let report = Report::new(&garden, &["lost", "leaf"]);
+        assert_eq!(report.orphans, vec![Path::new("inner/home.md")]);
+        assert_eq!(report.dead_ends, vec![Path::new("inner/home.md")]);
+    }
+}
@@ -0,0 +1,220 @@
+use std::path::PathBuf;
+
+use generator::fs::AssetStrategy;
+use thiserror::Error;
+
+pub const USAGE: &str = "\
+Usage: generator [OPTIONS] [COMMAND]
+
+Commands:
+    build    Render every page into the target directory (the default)
+    check    Report pages that don't parse, broken interlinks, orphans and
+             dead ends without building anything
+    fmt      Rewrite every page into canonical form
+    graph    Print the link graph between pages as Graphviz DOT
+    watch    Build, then keep rebuilding the pages affected by every change
+             to the root or the template until stopped
+    serve    Watch, and serve the target over HTTP on localhost. Open pages
+             reload when they're rendered again. Without a target it builds
+             into a temporary directory
+
+Options:
+    -c, --config <PATH>      Configuration file to read. Paths in it are from
+                             its directory [default: generator.conf]
+        --root <PATH>        Where the pages are, instead of the `Root` key
+        --target <PATH>      Where to build to, instead of the `Target` key
+        --template <PATH>    The page template, instead of the `Template` key
+    -j, --jobs <N>           How many pages to parse at once [default: one for
+                             every processor]
+        --assets <HOW>       How files that aren't pages get into the target,
+                             instead of the `Assets` key: symlink, hardlink or
+                             copy [default: copy]
+        --check              fmt: only list the pages that would change
+        --json               graph, check: print JSON instead
+        --external           graph: include reference and absolute links
+        --port <PORT>        serve: the port to listen on [default: 4000]
+    -h, --help               Print this and exit
+
+Exit codes:
+    0    Everything went fine
+    1    There were problems with the pages, like broken links or pages
+         that don't parse, or fmt --check found pages to format
+    2    The command line was wrong
+    3    The configuration was missing something or couldn't be read
+";
+
+pub const DEFAULT_PORT: u16 = 4000;
+
+pub const EXIT_PROBLEMS: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_CONFIG: i32 = 3;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Cli {
+    pub command: Command,
+    pub config: Option<PathBuf>,
+    pub root: Option<PathBuf>,
+    pub target: Option<PathBuf>,
+    pub template: Option<PathBuf>,
+    pub check: bool,
+    pub json: bool,
+    pub external: bool,
+    pub port: Option<u16>,
+    pub jobs: Option<usize>,
+    pub assets: Option<AssetStrategy>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Command {
+    #[default]
+    Build,
+    Check,
+    Fmt,
+    Graph,
+    Watch,
+    Serve,
+    Help,
+}
+
+impl Cli {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, CliError> {
+        let mut cli = Cli::default();
+        let mut command = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // Both `--root path` and `--root=path` work
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_owned())),
+                _ => (arg.as_str(), None),
+            };
+
+            let mut value = || match inline_value.clone().or_else(|| args.next()) {
+                Some(value) => Ok(value),
+                None => Err(CliError::MissingValue(flag.to_owned())),
+            };
+
+            match flag {
+                "-c" | "--config" => cli.config = Some(value()?.into()),
+                "--root" => cli.root = Some(value()?.into()),
+                "--target" => cli.target = Some(value()?.into()),
+                "--template" => cli.template = Some(value()?.into()),
+                "--port" => {
+                    let port = value()?;
+                    match port.parse() {
+                        Ok(port) => cli.port = Some(port),
+                        Err(_) => return Err(CliError::InvalidValue(flag.to_owned(), port)),
+                    }
+                }
+                "-j" | "--jobs" => {
+                    let jobs = value()?;
+                    match jobs.parse() {
+                        Ok(0) | Err(_) => {
+                            return Err(CliError::InvalidValue(flag.to_owned(), jobs))
+                        }
+                        Ok(jobs) => cli.jobs = Some(jobs),
+                    }
+                }
+                "--assets" => {
+                    let assets = value()?;
+                    match assets.parse() {
+                        Ok(assets) => cli.assets = Some(assets),
+                        Err(_) => return Err(CliError::InvalidValue(flag.to_owned(), assets)),
+                    }
+                }
+                "--check" => cli.check = true,
+                "--json" => cli.json = true,
+                "--external" => cli.external = true,
+                "-h" | "--help" => command = Some(Command::Help),
+                flag if flag.starts_with('-') => {
+                    return Err(CliError::UnknownOption(flag.to_owned()))
+                }
+                _ if command.is_some() => return Err(CliError::UnexpectedArgument(arg)),
+                "build" => command = Some(Command::Build),
+                "check" => command = Some(Command::Check),
+                "fmt" => command = Some(Command::Fmt),
+                "graph" => command = Some(Command::Graph),
+                "watch" => command = Some(Command::Watch),
+                "serve" => command = Some(Command::Serve),
+                _ => return Err(CliError::UnknownCommand(arg)),
+            }
+        }
+
+        cli.command = command.unwrap_or_default();
+        Ok(cli)
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CliError {
+    #[error("{0} is not a command")]
+    UnknownCommand(String),
+    #[error("{0} is not an option")]
+    UnknownOption(String),
+    #[error("{0} needs a value")]
+    MissingValue(String),
+    #[error("{1} is not a valid value for {0}")]
+    InvalidValue(String, String),
+    #[error("did not expect {0}, there's already a command")]
+    UnexpectedArgument(String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, CliError> {
+        Cli::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn defaults_to_build() {
+        assert_eq!(parse(&[]).unwrap(), Cli::default());
+        assert_eq!(parse(&[]).unwrap().command, Command::Build);
+    }
+
+    #[test]
+    fn options_and_command() {
+        let cli = parse(&["--config", "a.conf", "graph", "--root=pages", "--json"]).unwrap();
+
+        assert_eq!(cli.command, Command::Graph);
+        assert_eq!(cli.config, Some(PathBuf::from("a.conf")));
+        assert_eq!(cli.root, Some(PathBuf::from("pages")));
+        assert!(cli.json);
+        assert_eq!(cli.assets, None);
+        assert_eq!(
+            parse(&["--assets=hardlink"]).unwrap().assets,
+            Some(AssetStrategy::Hardlink)
+        );
+        assert!(!cli.external);
+    }
+
+    #[test]
+    fn bad_arguments() {
+        assert_eq!(
+            parse(&["--target"]),
+            Err(CliError::MissingValue("--target".into()))
+        );
+        assert_eq!(
+            parse(&["--nope"]),
+            Err(CliError::UnknownOption("--nope".into()))
+        );
+        assert_eq!(
+            parse(&["bulid"]),
+            Err(CliError::UnknownCommand("bulid".into()))
+        );
+        assert_eq!(
+            parse(&["-j", "0"]),
+            Err(CliError::InvalidValue("-j".into(), "0".into()))
+        );
+        assert_eq!(
+            parse(&["--port", "http"]),
+            Err(CliError::InvalidValue("--port".into(), "http".into()))
+        );
+        assert_eq!(
+            parse(&["fmt", "check"]),
+            Err(CliError::UnexpectedArgument("check".into()))
+        );
+        assert_eq!(parse(&["fmt", "-h"]).unwrap().command, Command::Help);
+    }
+}
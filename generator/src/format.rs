@@ -0,0 +1,101 @@
+use parser::{
+    element::{Inline, ListItem, Span},
+    visit::{self, VisitorMut},
+    ParseError, Parser,
+};
+
+/// Rewrites a page's source into canonical form. Reference definitions move to
+/// the end of the page, in the order they're first used, and whitespace that
+/// doesn't change anything is taken out.
+pub fn format_source<S: AsRef<str>>(source: S) -> Result<String, ParseError> {
+    let mut parser: Parser = source.as_ref().parse()?;
+    parser.visit_mut(&mut Tidy);
+
+    Ok(parser.to_string())
+}
+
+// Takes off whitespace at the end of lines and between a header's `#` and its
+// text. The printer does the rest.
+struct Tidy;
+
+impl VisitorMut for Tidy {
+    fn visit_header_mut(&mut self, _level: &mut u8, content: &mut Vec<Inline>, _span: &mut Span) {
+        if let Some(Inline::Text(txt, _)) = content.first_mut() {
+            *txt = txt.trim_start().to_owned();
+        }
+
+        trim_line_ends(content);
+        visit::walk_inlines_mut(self, content)
+    }
+
+    fn visit_paragraph_mut(&mut self, content: &mut Vec<Inline>, _span: &mut Span) {
+        trim_line_ends(content);
+        visit::walk_inlines_mut(self, content)
+    }
+
+    fn visit_code_block_mut(
+        &mut self,
+        _language: &mut String,
+        content: &mut String,
+        _span: &mut Span,
+    ) {
+        *content = content
+            .lines()
+            .map(|line| format!("{}\n", line.trim_end()))
+            .collect();
+    }
+
+    fn visit_image_mut(&mut self, _link: &mut Inline, alt: &mut String, _span: &mut Span) {
+        *alt = alt.trim_end().to_owned();
+    }
+
+    fn visit_list_item_mut(&mut self, item: &mut ListItem) {
+        trim_line_ends(&mut item.content);
+        visit::walk_inlines_mut(self, &mut item.content);
+        visit::walk_blocks_mut(self, &mut item.children);
+    }
+
+    fn visit_footnote_mut(&mut self, _name: &str, content: &mut Vec<Inline>) {
+        trim_line_ends(content);
+        visit::walk_inlines_mut(self, content)
+    }
+}
+
+fn trim_line_ends(content: &mut Vec<Inline>) {
+    for idx in 0..content.len() {
+        let line_end = matches!(content.get(idx + 1), None | Some(Inline::SoftBreak(_)));
+
+        if let (true, Inline::Text(txt, _)) = (line_end, &mut content[idx]) {
+            *txt = txt.trim_end().to_owned();
+        }
+    }
+
+    content.retain(|inline| !matches!(inline, Inline::Text(txt, _) if txt.is_empty()));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalises() {
+        let source = "#   Title  \n[ref]: https://example.com\nA line   \nand {!ref}\t\n";
+
+        assert_eq!(
+            format_source(source).unwrap(),
+            "# Title\n\nA line\nand {!ref}\n\n[ref]: https://example.com\n"
+        );
+    }
+
+    #[test]
+    fn formatted_is_unchanged() {
+        let source = "# Title\n\n- one\n  - two\n\n```\ncode\n```\n";
+
+        assert_eq!(format_source(source).unwrap(), source);
+    }
+
+    #[test]
+    fn refuses_unclosed_code() {
+        assert!(format_source("Some `code\n").is_err());
+    }
+}
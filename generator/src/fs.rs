@@ -1,10 +1,102 @@
 use std::{
     collections::HashMap,
-    iter,
+    fs::File,
+    io, iter,
     path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 use thiserror::Error;
 
+/// How the files that aren't pages get into the target
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AssetStrategy {
+    /// Links back into root, which only work on the machine they were made on
+    Symlink,
+    Hardlink,
+    /// The only one that makes a target that stands on its own
+    #[default]
+    Copy,
+}
+
+impl FromStr for AssetStrategy {
+    type Err = UnknownAssetStrategy;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "symlink" => Ok(AssetStrategy::Symlink),
+            "hardlink" => Ok(AssetStrategy::Hardlink),
+            "copy" => Ok(AssetStrategy::Copy),
+            _ => Err(UnknownAssetStrategy(raw.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error("{0} is not a way to place assets, it can be symlink, hardlink or copy")]
+pub struct UnknownAssetStrategy(pub String);
+
+impl AssetStrategy {
+    /// Puts the file at outpath unless what's there already is the same. Gives
+    /// back whether anything had to be done.
+    pub fn place(self, file: &Path, outpath: &Path) -> io::Result<bool> {
+        // Not followed, a link is what's there, not what it points at
+        let existing = std::fs::symlink_metadata(outpath).ok();
+
+        if let Some(existing) = &existing {
+            if self.is_placed(file, outpath, existing)? {
+                return Ok(false);
+            }
+
+            // Whatever is there has to go first. Copying over a link would
+            // write through it, into the file in root.
+            std::fs::remove_file(outpath)?;
+        }
+
+        match self {
+            AssetStrategy::Symlink => std::os::unix::fs::symlink(file, outpath)?,
+            AssetStrategy::Hardlink => std::fs::hard_link(file, outpath)?,
+            AssetStrategy::Copy => {
+                std::fs::copy(file, outpath)?;
+                // The same mtime is how it's known to be the same next time
+                let modified = std::fs::metadata(file)?.modified()?;
+                File::options()
+                    .write(true)
+                    .open(outpath)?
+                    .set_modified(modified)?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn is_placed(
+        self,
+        file: &Path,
+        outpath: &Path,
+        existing: &std::fs::Metadata,
+    ) -> io::Result<bool> {
+        use std::os::unix::fs::MetadataExt;
+
+        let source = std::fs::metadata(file)?;
+
+        Ok(match self {
+            AssetStrategy::Symlink => {
+                existing.file_type().is_symlink() && std::fs::read_link(outpath)? == file
+            }
+            AssetStrategy::Hardlink => {
+                existing.dev() == source.dev() && existing.ino() == source.ino()
+            }
+            AssetStrategy::Copy => {
+                existing.file_type().is_file()
+                    && existing.nlink() == 1
+                    && existing.len() == source.len()
+                    && existing.modified().ok() == source.modified().ok()
+            }
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct Directory {
     pub base: PathBuf,
@@ -53,10 +145,13 @@ impl Directory {
         Ok(ret)
     }
 
+    /// Makes the same directories under clone_to, placing the files the
+    /// closure says to with the asset strategy
     pub fn clone_structure<P: AsRef<Path>, F>(
         &self,
         clone_to: P,
-        should_symlink_file: F,
+        assets: AssetStrategy,
+        should_place_file: F,
     ) -> Result<(), DirectoryError>
     where
         F: Fn(&Path, &Path) -> bool + Clone,
@@ -73,10 +168,10 @@ impl Directory {
                 let mut outpath = clone_to.clone();
                 outpath.push(base_relative_name);
 
-                if should_symlink_file(&file, &outpath) {
-                    if !outpath.exists() {
-                        std::os::unix::fs::symlink(file, outpath).unwrap();
-                    }
+                if should_place_file(file, &outpath) {
+                    assets
+                        .place(file, &outpath)
+                        .map_err(|e| DirectoryError::Asset(file.to_owned(), e))?;
                 }
             }
         }
@@ -90,7 +185,7 @@ impl Directory {
             if !clone_to.exists() {
                 std::fs::create_dir(&clone_to)?;
             }
-            directory.clone_structure(&clone_to, should_symlink_file.clone())?;
+            directory.clone_structure(&clone_to, assets, should_place_file.clone())?;
         }
 
         Ok(())
@@ -109,6 +204,22 @@ impl Directory {
         ret
     }
 
+    /// Every file in this directory and the ones under it
+    pub fn all_files(&self) -> Vec<&Path> {
+        let mut ret: Vec<&Path> = self
+            .files_by_extension
+            .values()
+            .flatten()
+            .map(|pb| pb.as_path())
+            .collect();
+
+        for dir in &self.directories {
+            ret.extend_from_slice(&dir.all_files());
+        }
+
+        ret
+    }
+
     pub fn get_directory<P: AsRef<Path>>(&self, path: P) -> Option<&Directory> {
         let path = match path.as_ref().canonicalize().ok() {
             Some(s) => s,
@@ -147,6 +258,42 @@ pub enum DirectoryError {
     NotADirectory(PathBuf),
     #[error("{0}")]
     IoError(#[from] std::io::Error),
+    #[error("could not place {0} in the target: {1}")]
+    Asset(PathBuf, std::io::Error),
+}
+
+/// A new directory under the system's temporary directory, removed along with
+/// everything in it when this is dropped. The name is only to tell where it
+/// came from, every one is different.
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> io::Result<Self> {
+        static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = std::env::temp_dir().join(format!(
+            "generator-{}-{}-{}",
+            name,
+            std::process::id(),
+            CREATED.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+
+        // Absolute without any links, so it matches paths that were looked up
+        Ok(Self(dir.canonicalize()?))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // Nothing to be done if it can't be, it's in the temporary directory
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
 }
 
 //TODO: Maybe return a Result with an error type detailng why we failed.
@@ -193,6 +340,34 @@ pub fn relativise_path<A: AsRef<Path>, B: AsRef<Path>>(base: A, target: B) -> Op
 mod test {
     use super::*;
 
+    #[test]
+    fn copies_only_when_changed() {
+        let dir = TempDir::new("assets").unwrap();
+        let file = dir.path().join("style.css");
+        let outpath = dir.path().join("out.css");
+        std::fs::write(&file, "body {}").unwrap();
+
+        let copy = AssetStrategy::Copy;
+        assert!(copy.place(&file, &outpath).unwrap());
+        assert!(!copy.place(&file, &outpath).unwrap());
+
+        // A link left by another strategy is replaced, not written through
+        assert!(AssetStrategy::Symlink.place(&file, &outpath).unwrap());
+        assert!(!AssetStrategy::Symlink.place(&file, &outpath).unwrap());
+        assert!(copy.place(&file, &outpath).unwrap());
+        assert!(!std::fs::symlink_metadata(&outpath)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+
+        std::fs::write(&file, "body { margin: 0 }").unwrap();
+        assert!(copy.place(&file, &outpath).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&outpath).unwrap(),
+            "body { margin: 0 }"
+        );
+    }
+
     #[test]
     fn relativise_path_same_dir() {
         let base = PathBuf::from("/srv/wikarden/");
@@ -0,0 +1,270 @@
+use std::{collections::BTreeSet, path::Path};
+
+use crate::Garden;
+
+/// The garden's pages and the links between them, in a form that's easy to
+/// write out. Everything is sorted so the output is the same every time.
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    /// Pairs of node ids, from and to. A page linking to another many times is
+    /// still only one edge.
+    pub edges: Vec<(String, String)>,
+}
+
+pub struct Node {
+    /// A page's path from the root without the `.md`, or an external link's
+    /// location
+    pub id: String,
+    pub title: String,
+    /// The directory the page is in, relative to the root. Empty for pages in
+    /// the root and for external links.
+    pub directory: String,
+    pub external: bool,
+}
+
+impl Graph {
+    /// With external, reference and absolute links are in the graph too, each
+    /// location being a node of its own.
+    pub fn new(garden: &Garden, external: bool) -> Self {
+        let mut nodes: Vec<Node> = garden
+            .files
+            .iter()
+            .map(|file| Node {
+                id: page_id(garden.root, file),
+                title: garden.title(file).to_owned(),
+                directory: file
+                    .parent()
+                    .and_then(|dir| dir.strip_prefix(garden.root).ok())
+                    .map(|dir| dir.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                external: false,
+            })
+            .collect();
+
+        let mut edges: BTreeSet<(String, String)> = garden
+            .links
+            .iter()
+            .map(|link| {
+                (
+                    page_id(garden.root, &link.from),
+                    page_id(garden.root, &link.to),
+                )
+            })
+            .collect();
+
+        if external {
+            let mut locations = BTreeSet::new();
+
+            for link in &garden.external_links {
+                edges.insert((page_id(garden.root, &link.from), link.location.clone()));
+                locations.insert(link.location.clone());
+            }
+
+            nodes.extend(locations.into_iter().map(|location| Node {
+                id: location.clone(),
+                title: location,
+                directory: String::new(),
+                external: true,
+            }));
+        }
+
+        nodes.sort_by(|a, b| (a.external, &a.id).cmp(&(b.external, &b.id)));
+
+        Self {
+            nodes,
+            edges: edges.into_iter().collect(),
+        }
+    }
+
+    /// Graphviz. Pages in the same directory are clustered together and
+    /// external links are boxes.
+    pub fn dot(&self) -> String {
+        let mut ret = String::from("digraph garden {\n");
+
+        let directories: BTreeSet<&str> = self
+            .nodes
+            .iter()
+            .map(|node| node.directory.as_str())
+            .collect();
+
+        for directory in directories {
+            let nodes = self.nodes.iter().filter(|node| node.directory == directory);
+            // The root isn't a cluster, it's everything else
+            let indent = match directory {
+                "" => "\t",
+                _ => {
+                    ret.push_str(&format!(
+                        "\tsubgraph {} {{\n\t\tlabel={};\n",
+                        dot_string(&format!("cluster_{}", directory)),
+                        dot_string(directory)
+                    ));
+                    "\t\t"
+                }
+            };
+
+            for node in nodes {
+                let shape = if node.external { ", shape=box" } else { "" };
+                ret.push_str(&format!(
+                    "{}{} [label={}{}];\n",
+                    indent,
+                    dot_string(&node.id),
+                    dot_string(&node.title),
+                    shape
+                ));
+            }
+
+            if !directory.is_empty() {
+                ret.push_str("\t}\n");
+            }
+        }
+
+        for (from, to) in &self.edges {
+            ret.push_str(&format!("\t{} -> {};\n", dot_string(from), dot_string(to)));
+        }
+
+        ret.push_str("}\n");
+        ret
+    }
+
+    /// `{"nodes": [{"id", "title", "directory", "external"}], "edges": [{"from", "to"}]}`
+    pub fn json(&self) -> String {
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                format!(
+                    "{{\"id\":{},\"title\":{},\"directory\":{},\"external\":{}}}",
+                    json_string(&node.id),
+                    json_string(&node.title),
+                    json_string(&node.directory),
+                    node.external
+                )
+            })
+            .collect();
+
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|(from, to)| {
+                format!(
+                    "{{\"from\":{},\"to\":{}}}",
+                    json_string(from),
+                    json_string(to)
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"nodes\":[{}],\"edges\":[{}]}}\n",
+            nodes.join(","),
+            edges.join(",")
+        )
+    }
+}
+
+// Pages are known by their path from the root, without the extension
+fn page_id(root: &Path, page: &Path) -> String {
+    let relative = page.strip_prefix(root).unwrap_or(page);
+    relative.with_extension("").to_string_lossy().into_owned()
+}
+
+fn dot_string(raw: &str) -> String {
+    format!("\"{}\"", raw.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+pub(crate) fn json_string(raw: &str) -> String {
+    let mut ret = String::from("\"");
+
+    for ch in raw.chars() {
+        match ch {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            ch if ch.is_control() => ret.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => ret.push(ch),
+        }
+    }
+
+    ret.push('"');
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ExternalLink, Link};
+    use std::{collections::HashMap, path::PathBuf};
+
+    fn garden<'a>(files: &'a [&'a Path]) -> Garden<'a> {
+        let mut titles = HashMap::new();
+        titles.insert(
+            PathBuf::from("/wiki/home.md"),
+            String::from("Home \"Page\""),
+        );
+
+        Garden {
+            root: Path::new("/wiki"),
+            files,
+            headings: HashMap::new(),
+            titles,
+            links: vec![
+                Link {
+                    from: PathBuf::from("/wiki/home.md"),
+                    to: PathBuf::from("/wiki/inner/notes.md"),
+                    excerpt: String::new(),
+                },
+                Link {
+                    from: PathBuf::from("/wiki/home.md"),
+                    to: PathBuf::from("/wiki/inner/notes.md"),
+                    excerpt: String::new(),
+                },
+            ],
+            external_links: vec![ExternalLink {
+                from: PathBuf::from("/wiki/inner/notes.md"),
+                location: String::from("https://example.com"),
+            }],
+        }
+    }
+
+    #[test]
+    fn dot_clusters_directories() {
+        let files = [
+            Path::new("/wiki/home.md"),
+            Path::new("/wiki/inner/notes.md"),
+        ];
+        let graph = Graph::new(&garden(&files), false);
+
+        assert_eq!(
+            graph.dot(),
+            "digraph garden {\n\
+            \t\"home\" [label=\"Home \\\"Page\\\"\"];\n\
+            \tsubgraph \"cluster_inner\" {\n\
+            \t\tlabel=\"inner\";\n\
+            \t\t\"inner/notes\" [label=\"notes\"];\n\
+            \t}\n\
+            \t\"home\" -> \"inner/notes\";\n\
+            }\n"
+        );
+    }
+
+    #[test]
+    fn json_with_external() {
+        let files = [
+            Path::new("/wiki/home.md"),
+            Path::new("/wiki/inner/notes.md"),
+        ];
+        let graph = Graph::new(&garden(&files), true);
+
+        assert_eq!(
+            graph.json(),
+            "{\"nodes\":[\
+            {\"id\":\"home\",\"title\":\"Home \\\"Page\\\"\",\"directory\":\"\",\"external\":false},\
+            {\"id\":\"inner/notes\",\"title\":\"notes\",\"directory\":\"inner\",\"external\":false},\
+            {\"id\":\"https://example.com\",\"title\":\"https://example.com\",\"directory\":\"\",\"external\":true}],\
+            \"edges\":[{\"from\":\"home\",\"to\":\"inner/notes\"},\
+            {\"from\":\"inner/notes\",\"to\":\"https://example.com\"}]}\n"
+        );
+    }
+}
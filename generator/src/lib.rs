@@ -1,118 +1,703 @@
+pub mod check;
+pub mod format;
 pub mod fs;
+pub mod graph;
+pub mod manifest;
+pub mod resolve;
+pub mod serve;
+pub mod watch;
 
 use std::{
-    iter,
+    collections::HashMap,
+    fmt,
     path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
 };
 
+use resolve::Resolution;
+
 use parser::{
-    element::{Block, Inline},
-    Parser,
+    element::{Alignment, Block, Inline, ListItem, Span, TableRow},
+    visit::{self, Visitor, VisitorMut},
+    Meta, ParseError, Parser, Position,
 };
 
-pub fn parse_file<P: AsRef<Path>>(path: P, files: &[&Path]) -> (String, String) {
-    let txt = std::fs::read_to_string(path.as_ref()).unwrap();
-    let mut parser: Parser = txt.parse().unwrap();
+pub struct Page {
+    pub title: String,
+    pub body: String,
+    /// Everything from the page's metadata block, in the order it was written
+    pub metadata: Vec<Meta>,
+    /// Interlinks that didn't lead to exactly one page
+    pub broken_links: Vec<BrokenLink>,
+}
+
+#[derive(Debug)]
+pub struct BrokenLink {
+    /// The page the link is on
+    pub file: PathBuf,
+    pub position: Position,
+    pub location: String,
+    pub problem: LinkProblem,
+}
+
+#[derive(Debug)]
+pub enum LinkProblem {
+    /// No page has this name. Suggestions are the names of the pages that come
+    /// close, closest first.
+    Missing { suggestions: Vec<String> },
+    /// More than one page could be the one meant
+    Ambiguous { candidates: Vec<PathBuf> },
+    /// The page is there but the heading isn't
+    MissingHeading { page: PathBuf, heading: String },
+}
+
+impl fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: ", self.file.display(), self.position)?;
+
+        match &self.problem {
+            LinkProblem::Missing { suggestions } => {
+                write!(f, "no page named {}", self.location)?;
 
-    // inlines_mut is broken and won't go to the full depth so this is a little
-    // hacky workaround
-    for inline in parser.inlines_mut() {
-        make_interlinks(inline, files, path.as_ref());
+                if !suggestions.is_empty() {
+                    write!(f, ", did you mean {}?", suggestions.join(", "))?;
+                }
+                Ok(())
+            }
+            LinkProblem::Ambiguous { candidates } => {
+                write!(f, "{} could be any of", self.location)?;
+                for candidate in candidates {
+                    write!(f, "\n\t{}", candidate.display())?;
+                }
+                Ok(())
+            }
+            LinkProblem::MissingHeading { page, heading } => {
+                write!(f, "{} has no heading {}", page.display(), heading)
+            }
+        }
     }
+}
 
-    let mut ret = String::new();
+/// Everything about the garden as a whole that rendering one page needs to
+/// know. All of the paths are absolute.
+pub struct Garden<'a> {
+    pub root: &'a Path,
+    /// Every page under root
+    pub files: &'a [&'a Path],
+    /// The ids the headers of every page will get, by the page's path
+    pub headings: HashMap<PathBuf, Vec<String>>,
+    /// The title of every page as plain text, by the page's path
+    pub titles: HashMap<PathBuf, String>,
+    /// Every interlink that leads to a page other than the one it's on
+    pub links: Vec<Link>,
+    /// Every reference and absolute link, which lead out of the garden
+    pub external_links: Vec<ExternalLink>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Link {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// The text of the paragraph, or list item or the like, the link is in
+    pub excerpt: String,
+}
+
+#[derive(Debug)]
+pub struct ExternalLink {
+    pub from: PathBuf,
+    pub location: String,
+}
+
+impl<'a> Garden<'a> {
+    /// Goes through every page, as [read_pages] gave them, for its headers,
+    /// title and links. Pages that weren't read or don't parse don't have any;
+    /// they'll be reported when they're rendered.
+    pub fn new(root: &'a Path, files: &'a [&'a Path], sources: &HashMap<PathBuf, Source>) -> Self {
+        let mut headings = HashMap::new();
+        let mut titles = HashMap::new();
+        let mut links = vec![];
+        let mut external_links = vec![];
+
+        for file in files {
+            let parser = match sources.get(*file).map(|source| &source.parsed) {
+                Some(Ok(parser)) => parser,
+                _ => continue,
+            };
+
+            let mut headers = Headers::default();
+            parser.visit(&mut headers);
+            headings.insert(file.to_path_buf(), headers.found);
+            titles.insert(file.to_path_buf(), plain_title(parser, file));
+
+            let mut linker = Linker {
+                root,
+                files,
+                page: file,
+                excerpt: String::new(),
+                links: &mut links,
+                external_links: &mut external_links,
+            };
+            parser.visit(&mut linker);
+        }
+
+        Self {
+            root,
+            files,
+            headings,
+            titles,
+            links,
+            external_links,
+        }
+    }
+
+    /// A list of every page that links to this one, along with what it said
+    /// around the link. Empty if nothing links here.
+    pub fn backlinks_html(&self, page: &Path) -> String {
+        let mut from: Vec<&Link> = self.links.iter().filter(|link| link.to == page).collect();
+        from.sort_by(|a, b| a.from.cmp(&b.from));
+        // One mention from every page is plenty
+        from.dedup_by(|a, b| a.from == b.from);
 
-    let mut block_iter = parser.blocks.into_iter();
-    let title = match block_iter.next() {
-        Some(Block::Header { level, content }) if level == 1 => vec_inline_html(content),
-        Some(block) => {
-            ret.push_str(&block_html(block));
-            String::new()
+        if from.is_empty() {
+            return String::new();
         }
-        None => String::new(),
+
+        let mut ret = String::from("<ul class=\"backlinks\">\n");
+        for link in from {
+            let mut href = fs::relativise_path(page, &link.from).unwrap();
+            href.set_extension("html");
+
+            ret.push_str(&format!(
+                "<li><a href=\"{}\">{}</a> <span class=\"excerpt\">{}</span></li>\n",
+                href.to_string_lossy(),
+                html_escape(self.title(&link.from)),
+                html_escape(&link.excerpt)
+            ));
+        }
+        ret.push_str("</ul>\n");
+
+        ret
+    }
+
+    /// The page's title, or its file name if it doesn't have one
+    pub fn title<'p>(&'p self, page: &'p Path) -> &'p str {
+        match self.titles.get(page) {
+            Some(title) if !title.is_empty() => title,
+            _ => page
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+// The title the page will be rendered with, without any of the markup
+fn plain_title(parser: &Parser, path: &Path) -> String {
+    match (parser.meta("Title"), parser.blocks.first()) {
+        (Some(title), _) => title.to_owned(),
+        (
+            None,
+            Some(Block::Header {
+                level: 1, content, ..
+            }),
+        ) => plain_text(content),
+        _ => path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    }
+}
+
+// Collects the interlinks on a page that resolve to another page, and every
+// link that goes somewhere else entirely
+struct Linker<'a, 'l> {
+    root: &'a Path,
+    files: &'a [&'a Path],
+    page: &'a Path,
+    // The text of whatever we're in the middle of
+    excerpt: String,
+    links: &'l mut Vec<Link>,
+    external_links: &'l mut Vec<ExternalLink>,
+}
+
+impl<'a, 'l> Visitor for Linker<'a, 'l> {
+    fn visit_header(&mut self, _level: u8, content: &[Inline], _span: Span) {
+        self.excerpt = excerpt(content);
+        visit::walk_inlines(self, content)
+    }
+
+    fn visit_paragraph(&mut self, content: &[Inline], _span: Span) {
+        self.excerpt = excerpt(content);
+        visit::walk_inlines(self, content)
+    }
+
+    fn visit_table_row(&mut self, row: &TableRow) {
+        for cell in &row.cells {
+            self.excerpt = excerpt(cell);
+            visit::walk_inlines(self, cell);
+        }
+    }
+
+    fn visit_list_item(&mut self, item: &ListItem) {
+        self.excerpt = excerpt(&item.content);
+        visit::walk_inlines(self, &item.content);
+        visit::walk_blocks(self, &item.children);
+    }
+
+    fn visit_footnote(&mut self, _name: &str, content: &[Inline]) {
+        self.excerpt = excerpt(content);
+        visit::walk_inlines(self, content)
+    }
+
+    fn visit_absolute_link(&mut self, _name: Option<&str>, location: &str, _span: Span) {
+        self.external(location)
+    }
+
+    fn visit_reference_link(&mut self, _name: &str, _ref: &str, location: &str, _span: Span) {
+        self.external(location)
+    }
+
+    fn visit_interlink(&mut self, _name: &str, location: &str, _span: Span) {
+        let page = match location.split_once('#') {
+            Some((page, _)) => page.trim(),
+            None => location,
+        };
+
+        if page.is_empty() {
+            return;
+        }
+
+        if let Resolution::Found(to) = resolve::resolve(page, self.page, self.root, self.files) {
+            if to != self.page {
+                self.links.push(Link {
+                    from: self.page.to_owned(),
+                    to: to.to_owned(),
+                    excerpt: self.excerpt.clone(),
+                });
+            }
+        }
+    }
+}
+
+impl<'a, 'l> Linker<'a, 'l> {
+    fn external(&mut self, location: &str) {
+        self.external_links.push(ExternalLink {
+            from: self.page.to_owned(),
+            location: location.to_owned(),
+        });
+    }
+}
+
+// Short enough to show in a list of other things
+fn excerpt(content: &[Inline]) -> String {
+    const MAX_CHARS: usize = 120;
+
+    let text = plain_text(content);
+    match text.char_indices().nth(MAX_CHARS) {
+        Some((idx, _)) => format!("{}...", text[..idx].trim_end()),
+        None => text,
+    }
+}
+
+/// A page as it was read, and what it parsed into. Every page is parsed once
+/// and the same tree goes to the [Garden] and then into the HTML.
+pub struct Source {
+    pub text: String,
+    pub parsed: Result<Parser, ParseError>,
+}
+
+/// Reads and parses every page, with as many threads as there are jobs. Pages
+/// that can't be read are left out.
+pub fn read_pages(paths: &[&Path], jobs: usize) -> HashMap<PathBuf, Source> {
+    let read = in_parallel(paths.to_vec(), jobs, |path| {
+        let text = std::fs::read_to_string(path).ok()?;
+        let parsed = text.parse();
+        Some((path.to_path_buf(), Source { text, parsed }))
+    });
+
+    read.into_iter().flatten().collect()
+}
+
+pub fn render_page(path: &Path, source: Source, garden: &Garden) -> Result<Page, ParseError> {
+    let mut parser = source.parsed?;
+
+    let mut interlinker = Interlinker {
+        garden,
+        path,
+        source: &source.text,
+        broken: vec![],
+    };
+    parser.visit_mut(&mut interlinker);
+    let broken_links = interlinker.broken;
+
+    let mut ret = String::new();
+    let mut render = Render::default();
+
+    let meta_title = parser.meta("Title").map(html_escape);
+    let mut block_iter = parser.blocks.into_iter().peekable();
+    let title = match meta_title {
+        Some(title) => title,
+        // Without a title in the metadata, a leading H1 is the best we've got
+        None => match block_iter.next_if(|block| matches!(block, Block::Header { level: 1, .. })) {
+            Some(Block::Header { content, .. }) => {
+                // It still takes its id so the rest match up with Headers
+                render.header_ids.next(&content);
+                vec_inline_html(content, &mut render)
+            }
+            _ => String::new(),
+        },
     };
 
     for block in block_iter {
-        ret.push_str(&block_html(block));
+        ret.push_str(&block_html(block, &mut render));
     }
+    ret.push_str(&footnotes_html(parser.footnotes, &mut render));
 
-    (title, ret)
+    Ok(Page {
+        title,
+        body: ret,
+        metadata: parser.metadata,
+        broken_links,
+    })
 }
 
-fn make_interlinks<P: AsRef<Path>>(inline: &mut Inline, files: &[&Path], path: P) {
-    match inline {
-        Inline::Italic { content } => {
-            for inline in content {
-                make_interlinks(inline, files, path.as_ref());
+/// [render_page] for every page, with as many threads as there are jobs. The
+/// pages are in the same order they were given in no matter how many jobs
+/// there are.
+pub fn render_pages(
+    pages: Vec<(&Path, Source)>,
+    garden: &Garden,
+    jobs: usize,
+) -> Vec<Result<Page, ParseError>> {
+    in_parallel(pages, jobs, |(path, source)| {
+        render_page(path, source, garden)
+    })
+}
+
+// Does work on every item, with each of the threads taking whichever item is
+// next until there are none left. The results are in the order of the items.
+fn in_parallel<T, R, F>(items: Vec<T>, jobs: usize, work: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let threads = jobs.clamp(1, items.len().max(1));
+    let next = Mutex::new(items.into_iter().enumerate());
+
+    let mut done: Vec<(usize, R)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = vec![];
+                    loop {
+                        // Let go of the lock before the work so others can go on
+                        let item = next.lock().unwrap().next();
+                        match item {
+                            Some((idx, item)) => done.push((idx, work(item))),
+                            None => return done,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+
+    done.sort_by_key(|(idx, _)| *idx);
+    done.into_iter().map(|(_, result)| result).collect()
+}
+
+// Points every interlink at the HTML file of the page it names, and the
+// heading on it if there is one. Links that don't lead anywhere are left with
+// an empty location, which the parser never gives a link, so they can be
+// rendered as broken.
+struct Interlinker<'a> {
+    garden: &'a Garden<'a>,
+    path: &'a Path,
+    source: &'a str,
+    broken: Vec<BrokenLink>,
+}
+
+impl<'a> Interlinker<'a> {
+    fn broken(&mut self, location: &mut String, span: &Span, problem: LinkProblem) {
+        self.broken.push(BrokenLink {
+            file: self.path.to_owned(),
+            position: Position::from_offset(self.source, span.start),
+            location: std::mem::take(location),
+            problem,
+        });
+    }
+}
+
+impl<'a> VisitorMut for Interlinker<'a> {
+    // Images link to files, not pages, so there's nothing to resolve
+    fn visit_image_mut(&mut self, _link: &mut Inline, _alt: &mut String, _span: &mut Span) {}
+
+    fn visit_interlink_mut(&mut self, _name: &mut String, location: &mut String, span: &mut Span) {
+        let garden = self.garden;
+        let (page, heading) = match location.split_once('#') {
+            Some((page, heading)) => (page.trim(), Some(heading.trim())),
+            None => (location.as_str(), None),
+        };
+
+        // `{#Heading}` is on this page
+        let (found, mut resolved) = if page.is_empty() {
+            (self.path, String::new())
+        } else {
+            match resolve::resolve(page, self.path, garden.root, garden.files) {
+                Resolution::Found(found) => {
+                    let mut relative = fs::relativise_path(self.path, found).unwrap();
+                    relative.set_extension("html");
+
+                    (found, relative.to_string_lossy().to_string())
+                }
+                Resolution::Missing => {
+                    let suggestions = suggestions(page, garden.files);
+                    return self.broken(location, span, LinkProblem::Missing { suggestions });
+                }
+                Resolution::Ambiguous(found) => {
+                    let candidates = found.into_iter().map(Path::to_path_buf).collect();
+                    return self.broken(location, span, LinkProblem::Ambiguous { candidates });
+                }
             }
-        }
-        Inline::Bold { content } => {
-            for inline in content {
-                make_interlinks(inline, files, path.as_ref());
+        };
+
+        if let Some(heading) = heading {
+            let id = slug(heading);
+            let exists = garden
+                .headings
+                .get(found)
+                .map(|ids| ids.contains(&id))
+                .unwrap_or(false);
+
+            if !exists {
+                let problem = LinkProblem::MissingHeading {
+                    page: found.to_owned(),
+                    heading: heading.to_owned(),
+                };
+                return self.broken(location, span, problem);
             }
+
+            resolved.push('#');
+            resolved.push_str(&id);
         }
-        Inline::InterLink { location, name } => {
-            println!("{} | {}", location, name);
-            let found: Vec<&&Path> = files
-                .iter()
-                .filter(|p| p.ends_with(&format!("{}.md", location)))
-                .collect();
 
-            if found.len() != 1 {
-                dbg!(location, found);
-                panic!("Found files does not have a length of one!");
+        *location = resolved;
+    }
+}
+
+// The names of the pages closest to location. A name that only differs in case
+// always counts, otherwise it has to be a few edits away at most.
+fn suggestions(location: &str, files: &[&Path]) -> Vec<String> {
+    const MAX_SUGGESTIONS: usize = 3;
+
+    // Only the name of the page is compared, not any directories before it
+    let wanted = location
+        .rsplit('/')
+        .next()
+        .unwrap_or(location)
+        .to_lowercase();
+    let allowed = (wanted.chars().count() / 3).max(1);
+
+    let mut close: Vec<(usize, String)> = files
+        .iter()
+        .filter_map(|file| file.file_stem())
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .filter_map(|name| {
+            let distance = edit_distance(&wanted, &name.to_lowercase());
+            if distance <= allowed {
+                Some((distance, name))
+            } else {
+                None
             }
+        })
+        .collect();
+
+    close.sort();
+    close.dedup_by(|a, b| a.1 == b.1);
+
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, name)| name)
+        .collect()
+}
 
-            let mut relative = fs::relativise_path(path.as_ref(), found[0]).unwrap();
-            relative.set_extension("html");
+// How many characters have to be inserted, removed or swapped out to turn one
+// string into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // The distances from the part of a we've seen to every prefix of b
+    let mut row: Vec<usize> = (0..=b.len()).collect();
 
-            *location = relative.to_string_lossy().to_string();
+    for (i, ach) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, bch) in b.iter().enumerate() {
+            let substitution = diagonal + if ach == *bch { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
         }
-        _ => (),
     }
+
+    row[b.len()]
 }
 
-fn block_html(block: Block) -> String {
+fn block_html(block: Block, render: &mut Render) -> String {
     match block {
-        Block::Header { level, content } => {
+        Block::Header { level, content, .. } => {
             format!(
-                "<h{level}>{}</h{level}>\n",
-                vec_inline_html(content),
+                "<h{level} id=\"{}\">{}</h{level}>\n",
+                render.header_ids.next(&content),
+                vec_inline_html(content, render),
                 level = level
             )
         }
-        Block::Paragraph { content } => format!("<p>{}</p>\n", vec_inline_html(content)),
+        Block::Paragraph { content, .. } => {
+            format!("<p>{}</p>\n", vec_inline_html(content, render))
+        }
         Block::CodeBlock { content, .. } => {
             format!("<pre><code>{}</pre></code>\n", html_escape(content))
         }
-        Block::Image { src, alt } => format!("<img src=\"{}\" alt=\"{}\"/>\n", src, alt),
+        Block::Image { link, alt, .. } => format!(
+            "<img src=\"{}\" alt=\"{}\"/>\n",
+            link.location().unwrap_or_default(),
+            alt
+        ),
+        Block::Quote { content, .. } => {
+            let mut ret = String::from("<blockquote>\n");
+            for block in content {
+                ret.push_str(&block_html(block, render));
+            }
+            ret.push_str("</blockquote>\n");
+            ret
+        }
+        Block::Table {
+            header,
+            alignments,
+            rows,
+            ..
+        } => {
+            let mut ret = String::from("<table>\n");
+
+            if let Some(header) = header {
+                ret.push_str("<thead>\n");
+                ret.push_str(&table_row_html(header, "th", &alignments, render));
+                ret.push_str("</thead>\n");
+            }
+
+            ret.push_str("<tbody>\n");
+            for row in rows {
+                ret.push_str(&table_row_html(row, "td", &alignments, render));
+            }
+            ret.push_str("</tbody>\n</table>\n");
+
+            ret
+        }
+        Block::UnorderedList { items, .. } => {
+            format!("<ul>\n{}</ul>\n", list_items_html(items, render))
+        }
+        Block::OrderedList {
+            start: 1, items, ..
+        } => {
+            format!("<ol>\n{}</ol>\n", list_items_html(items, render))
+        }
+        Block::OrderedList { start, items, .. } => {
+            format!(
+                "<ol start=\"{}\">\n{}</ol>\n",
+                start,
+                list_items_html(items, render)
+            )
+        }
+    }
+}
+
+fn table_row_html(
+    row: TableRow,
+    cell_tag: &str,
+    alignments: &[Alignment],
+    render: &mut Render,
+) -> String {
+    let mut ret = String::from("<tr>");
+
+    for (idx, cell) in row.cells.into_iter().enumerate() {
+        let style = match alignments.get(idx) {
+            Some(Alignment::Left) => " style=\"text-align: left\"",
+            Some(Alignment::Center) => " style=\"text-align: center\"",
+            Some(Alignment::Right) => " style=\"text-align: right\"",
+            Some(Alignment::None) | None => "",
+        };
+
+        ret.push_str(&format!(
+            "<{tag}{}>{}</{tag}>",
+            style,
+            vec_inline_html(cell, render),
+            tag = cell_tag
+        ));
     }
+
+    ret.push_str("</tr>\n");
+    ret
 }
 
-fn vec_inline_html(vecinline: Vec<Inline>) -> String {
+fn list_items_html(items: Vec<ListItem>, render: &mut Render) -> String {
+    let mut ret = String::new();
+
+    for item in items {
+        ret.push_str("<li>");
+        ret.push_str(&vec_inline_html(item.content, render));
+
+        if !item.children.is_empty() {
+            ret.push('\n');
+            for child in item.children {
+                ret.push_str(&block_html(child, render));
+            }
+        }
+
+        ret.push_str("</li>\n");
+    }
+
+    ret
+}
+
+fn vec_inline_html(vecinline: Vec<Inline>, render: &mut Render) -> String {
     let mut ret = String::new();
 
     for inline in vecinline {
-        ret.push_str(&inline_html(inline));
+        ret.push_str(&inline_html(inline, render));
     }
 
     ret
 }
 
-fn inline_html(inline: Inline) -> String {
+fn inline_html(inline: Inline, render: &mut Render) -> String {
     match inline {
-        Inline::SoftBreak => String::from("<br>"),
-        Inline::Text(txt) => html_escape(txt),
-        Inline::Code(code) => format!("<code>{}</code>", html_escape(code)),
-        Inline::Italic { content } => format!("<i>{}</i>", vec_inline_html(content)),
-        Inline::Bold { content } => format!("<b>{}</b>", vec_inline_html(content)),
-        Inline::InterLink { name, location } => {
+        Inline::SoftBreak(_) => String::from("<br>"),
+        Inline::Text(txt, _) => html_escape(txt),
+        Inline::Code(code, _) => format!("<code>{}</code>", html_escape(code)),
+        Inline::Italic { content, .. } => format!("<i>{}</i>", vec_inline_html(content, render)),
+        Inline::Bold { content, .. } => format!("<b>{}</b>", vec_inline_html(content, render)),
+        Inline::InterLink { name, location, .. } if location.is_empty() => {
+            format!("<a class=\"broken-link\">{}</a>", html_escape(name))
+        }
+        Inline::InterLink { name, location, .. } => {
             format!("<a href=\"{}\">{}</a>", location, name)
         }
-        Inline::ReferenceLink { name, location } => {
+        Inline::ReferenceLink { name, location, .. } => {
             format!("<a href=\"{}\">{}</a>", location, name)
         }
-        Inline::AbsoluteLink { name, location } => {
+        Inline::AbsoluteLink { name, location, .. } => {
             let name = match name {
                 Some(name) => name,
                 None => location.clone(),
@@ -120,9 +705,329 @@ fn inline_html(inline: Inline) -> String {
 
             format!("<a href=\"{}\">{}</a>", location, name)
         }
+        Inline::FootnoteReference { name, .. } => {
+            let (number, id) = render.notes.reference(&name);
+
+            format!(
+                "<sup id=\"{}\"><a href=\"#fn-{}\">{}</a></sup>",
+                id,
+                slug(&name),
+                number
+            )
+        }
+    }
+}
+
+// What has to be remembered from one part of the page to the next while it's
+// rendered
+#[derive(Default)]
+struct Render {
+    notes: Footnotes,
+    header_ids: HeaderIds,
+}
+
+// Every header is given an id from its text. Repeats get a number on the end.
+#[derive(Default)]
+struct HeaderIds {
+    seen: HashMap<String, usize>,
+}
+
+impl HeaderIds {
+    fn next(&mut self, content: &[Inline]) -> String {
+        let id = slug(&plain_text(content));
+        let count = self.seen.entry(id.clone()).or_insert(0);
+        *count += 1;
+
+        match *count {
+            1 => id,
+            count => format!("{}-{}", id, count),
+        }
+    }
+}
+
+// The ids of every header on a page, in the order they're rendered
+#[derive(Default)]
+struct Headers {
+    ids: HeaderIds,
+    found: Vec<String>,
+}
+
+impl Visitor for Headers {
+    fn visit_header(&mut self, _level: u8, content: &[Inline], _span: Span) {
+        self.found.push(self.ids.next(content));
+    }
+
+    // Headers can be in quotes but not in lists, so there's no need going in
+    fn visit_list_item(&mut self, _item: &ListItem) {}
+}
+
+// The text of some inlines without any of the markup
+fn plain_text(content: &[Inline]) -> String {
+    let mut ret = String::new();
+
+    for inline in content {
+        match inline {
+            Inline::SoftBreak(_) => ret.push(' '),
+            Inline::Text(txt, _) | Inline::Code(txt, _) => ret.push_str(txt),
+            Inline::Italic { content, .. } | Inline::Bold { content, .. } => {
+                ret.push_str(&plain_text(content))
+            }
+            Inline::AbsoluteLink {
+                name: Some(name), ..
+            } => ret.push_str(name),
+            Inline::AbsoluteLink { location, .. } => ret.push_str(location),
+            Inline::ReferenceLink { name, .. } | Inline::InterLink { name, .. } => {
+                ret.push_str(name)
+            }
+            Inline::FootnoteReference { .. } => (),
+        }
+    }
+
+    ret
+}
+
+// Footnotes are numbered in the order they're first referenced
+#[derive(Default)]
+struct Footnotes {
+    order: Vec<String>,
+    references: HashMap<String, usize>,
+}
+
+impl Footnotes {
+    // Gives back the footnote's number and an id for this reference to it.
+    // The first reference gets the id the footnote links back to.
+    fn reference(&mut self, name: &str) -> (usize, String) {
+        let count = self.references.entry(name.to_owned()).or_insert(0);
+        *count += 1;
+
+        if *count == 1 {
+            self.order.push(name.to_owned());
+        }
+
+        let number = self.order.iter().position(|n| n == name).unwrap() + 1;
+        let id = match *count {
+            1 => format!("fnref-{}", slug(name)),
+            count => format!("fnref-{}-{}", slug(name), count),
+        };
+
+        (number, id)
+    }
+}
+
+// The list of footnotes that goes at the end of the page. Only the ones that
+// were referenced make it in.
+fn footnotes_html(mut footnotes: HashMap<String, Vec<Inline>>, render: &mut Render) -> String {
+    let mut items = String::new();
+
+    // Footnotes can reference other footnotes, so order may grow as we go
+    let mut idx = 0;
+    while let Some(name) = render.notes.order.get(idx).cloned() {
+        idx += 1;
+
+        let content = footnotes.remove(&name).unwrap_or_default();
+        let slug = slug(&name);
+
+        items.push_str(&format!(
+            "<li id=\"fn-{slug}\">{} <a href=\"#fnref-{slug}\" class=\"footnote-back\">&#8617;</a></li>\n",
+            vec_inline_html(content, render),
+            slug = slug
+        ));
+    }
+
+    if items.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "<section class=\"footnotes\">\n<ol>\n{}</ol>\n</section>\n",
+            items
+        )
     }
 }
 
-fn html_escape<S: AsRef<str>>(raw: S) -> String {
+// Something safe to use in an id. Anything that isn't a letter or a number
+// becomes a single dash, and there are none at either end.
+fn slug(raw: &str) -> String {
+    let mut ret = String::with_capacity(raw.len());
+
+    // All at once, since some letters lowercase differently at the end of a word
+    for ch in raw.to_lowercase().chars() {
+        if ch.is_alphanumeric() {
+            ret.push(ch);
+        } else if !ret.is_empty() && !ret.ends_with('-') {
+            ret.push('-');
+        }
+    }
+
+    let trimmed = ret.trim_end_matches('-').len();
+    ret.truncate(trimmed);
+    ret
+}
+
+pub fn html_escape<S: AsRef<str>>(raw: S) -> String {
     raw.as_ref().replace("<", "&lt;").replace(">", "&gt;")
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fs::TempDir;
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("note", "notes"), 1);
+        assert_eq!(edit_distance("garden", "gardne"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slug("What's *this*, then?"), "what-s-this-then");
+        assert_eq!(slug("  Ünïcode -- Straße  "), "ünïcode-straße");
+        assert_eq!(slug("ΣΊΣΥΦΟΣ"), "σίσυφος");
+        assert_eq!(slug("?!"), "");
+    }
+
+    #[test]
+    fn suggests_close_pages() {
+        let files = [
+            Path::new("/wiki/Notes.md"),
+            Path::new("/wiki/project/todo.md"),
+            Path::new("/wiki/gardening.md"),
+        ];
+
+        assert_eq!(suggestions("notes", &files), vec!["Notes"]);
+        assert_eq!(suggestions("todos", &files), vec!["todo"]);
+        assert!(suggestions("elsewhere", &files).is_empty());
+    }
+
+    #[test]
+    fn header_ids_are_unique() {
+        let parser: Parser = "# Intro\n## The *first* part\n> ## Intro".parse().unwrap();
+        let mut headers = Headers::default();
+        parser.visit(&mut headers);
+
+        assert_eq!(headers.found, vec!["intro", "the-first-part", "intro-2"]);
+    }
+
+    // Writes the pages out to a directory of their own and gives it back along
+    // with the path of every page
+    fn write_garden(name: &str, pages: &[(&str, &str)]) -> (TempDir, Vec<PathBuf>) {
+        let root = TempDir::new(name).unwrap();
+
+        let paths = pages
+            .iter()
+            .map(|(name, source)| {
+                let path = root.path().join(name);
+                std::fs::write(&path, source).unwrap();
+                path
+            })
+            .collect();
+
+        (root, paths)
+    }
+
+    #[test]
+    fn links_to_headings() {
+        let (root, paths) = write_garden(
+            "headings",
+            &[
+                ("one.md", "# One\n\n{two#Second Part} {#One} {two#<Nope>}\n"),
+                ("two.md", "# Two\n\n## Second Part\n"),
+            ],
+        );
+
+        let files: Vec<&Path> = paths.iter().map(PathBuf::as_path).collect();
+        let mut sources = read_pages(&files, 1);
+        let garden = Garden::new(root.path(), &files, &sources);
+        let source = sources.remove(&paths[0]).unwrap();
+        let page = render_page(&paths[0], source, &garden).unwrap();
+
+        assert!(page.body.contains("<a href=\"two.html#second-part\">"));
+        assert!(page.body.contains("<a href=\"#one\">"));
+        assert!(page
+            .body
+            .contains("<a class=\"broken-link\">two#&lt;Nope&gt;</a>"));
+        match &page.broken_links[..] {
+            [BrokenLink {
+                problem: LinkProblem::MissingHeading { heading, .. },
+                ..
+            }] => assert_eq!(heading, "<Nope>"),
+            broken => panic!("expected one missing heading, got {:?}", broken),
+        }
+    }
+
+    #[test]
+    fn backlinks_with_excerpts() {
+        let (root, paths) = write_garden(
+            "backlinks",
+            &[
+                (
+                    "home.md",
+                    "# Home\n\nSee the {notes} for more.\n\n- Also {notes}\n",
+                ),
+                (
+                    "notes.md",
+                    "```metadata\nTitle My Notes\n```\n\nBack {home}, {notes}\n",
+                ),
+            ],
+        );
+
+        let files: Vec<&Path> = paths.iter().map(PathBuf::as_path).collect();
+        let garden = Garden::new(root.path(), &files, &read_pages(&files, 2));
+        let to_notes = garden.backlinks_html(&paths[1]);
+        let to_home = garden.backlinks_html(&paths[0]);
+
+        assert_eq!(
+            to_notes,
+            "<ul class=\"backlinks\">\n<li><a href=\"home.html\">Home</a> \
+            <span class=\"excerpt\">See the notes for more.</span></li>\n</ul>\n"
+        );
+        assert!(to_home.contains("<a href=\"notes.html\">My Notes</a>"));
+        assert_eq!(garden.links.len(), 3);
+    }
+
+    #[test]
+    fn parses_in_order_with_any_jobs() {
+        let pages: Vec<(String, String)> = (0..20)
+            .map(|idx| {
+                let source = match idx % 5 {
+                    // Some don't parse, and they have to stay where they are too
+                    0 => format!("*never closed {}", idx),
+                    _ => format!("# Page {}\n\n{{page{}}}\n", idx, (idx + 1) % 20),
+                };
+                (format!("page{}.md", idx), source)
+            })
+            .collect();
+        let pages: Vec<(&str, &str)> = pages
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let (root, paths) = write_garden("jobs", &pages);
+
+        let files: Vec<&Path> = paths.iter().map(PathBuf::as_path).collect();
+        let garden = Garden::new(root.path(), &files, &read_pages(&files, 4));
+        let summary = |jobs| -> Vec<Result<(String, usize), String>> {
+            let mut sources = read_pages(&files, jobs);
+            let pages = files
+                .iter()
+                .map(|file| (*file, sources.remove(*file).unwrap()))
+                .collect();
+
+            render_pages(pages, &garden, jobs)
+                .into_iter()
+                .map(|page| match page {
+                    Ok(page) => Ok((page.title, page.broken_links.len())),
+                    Err(e) => Err(e.to_string()),
+                })
+                .collect()
+        };
+
+        let one = summary(1);
+        let many = summary(8);
+
+        assert_eq!(one, many);
+        assert_eq!(one[1], Ok((String::from("Page 1"), 0)));
+        assert!(one[0].is_err() && one[5].is_err());
+    }
+}
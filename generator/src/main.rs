@@ -1,48 +1,457 @@
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap},
     fs::File,
-    io::Write,
+    io::{ErrorKind, Write},
+    num::NonZeroUsize,
     path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    thread,
+    time::Duration,
 };
 
 use bempline::{Document, Options};
 use confindent::Confindent;
-use generator::{fs::Directory, parse_file};
+use generator::{
+    check::Report,
+    format::format_source,
+    fs::{AssetStrategy, Directory, DirectoryError, TempDir},
+    graph::Graph,
+    html_escape,
+    manifest::{self, Manifest},
+    read_pages, render_pages,
+    serve::Server,
+    watch::{self, Changes, Outline, Stamps},
+    BrokenLink, Garden, Source,
+};
+use parser::ParseError;
+
+mod cli;
+
+use cli::{Cli, Command, DEFAULT_PORT, EXIT_CONFIG, EXIT_PROBLEMS, EXIT_USAGE, USAGE};
 
 fn main() {
-    let conf = match Confindent::from_file("generator.conf") {
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+
+    if cli.command == Command::Help {
+        print!("{}", USAGE);
+        return;
+    }
+
+    let conf = match load_config(cli.config.as_deref()) {
         Ok(conf) => conf,
         Err(e) => {
-            eprintln!("Could not parse the configuration file: {}", e);
-            std::process::exit(-1);
+            eprintln!("{}", e);
+            std::process::exit(EXIT_CONFIG);
         }
     };
 
-    let root = match conf.child_value("Root") {
-        Some(val) => PathBuf::from(val),
+    let value = |key: &str| conf.as_ref().and_then(|conf| conf.value(key));
+    let config_path = |key: &str| conf.as_ref().and_then(|conf| conf.path(key));
+
+    // Paths on the command line win over the ones in the configuration
+    let path = |given: &Option<PathBuf>, key: &str, flag: &str, what: &str| match given
+        .clone()
+        .or_else(|| config_path(key))
+    {
+        Some(path) => path,
         None => {
             eprintln!(
-                "Please specify where the wikarden root is in the coniguration with the `Root` key"
+                "Please specify {} with the configuration `{}` key or {}",
+                what, key, flag
             );
-            std::process::exit(-1);
+            std::process::exit(EXIT_CONFIG);
         }
     };
 
-    let target = match conf.child_value("Target") {
-        Some(val) => PathBuf::from(val),
-        None => {
-            eprintln!(
-                "Please specify where the HTML wikarden will be placed with the configuration `Target` key"
+    let root = path(&cli.root, "Root", "--root", "where the wikarden root is");
+
+    let assets = match (cli.assets, value("Assets").map(str::parse)) {
+        (Some(assets), _) => assets,
+        (None, Some(Ok(assets))) => assets,
+        (None, Some(Err(e))) => {
+            eprintln!("The configuration `Assets` key is wrong: {}", e);
+            std::process::exit(EXIT_CONFIG);
+        }
+        (None, None) => AssetStrategy::default(),
+    };
+
+    let settings = Settings {
+        // As many pages are parsed at once as there are processors
+        jobs: cli.jobs.unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map(NonZeroUsize::get)
+                .unwrap_or(1)
+        }),
+        assets,
+    };
+
+    let code = match cli.command {
+        Command::Fmt => format_pages(&root, cli.check),
+        Command::Graph => print_graph(&root, cli.json, cli.external, settings.jobs),
+        Command::Check => {
+            // Pages that are fine with nothing linking to them
+            let entry_points: Vec<&str> = match value("Entry") {
+                Some(entries) => entries.split_whitespace().collect(),
+                None => vec!["home"],
+            };
+
+            check_garden(&root, &entry_points, cli.json, settings.jobs)
+        }
+        Command::Serve => {
+            // Nothing has to be set up to have a look. A temporary target is
+            // gone once serving stops.
+            let temporary;
+            let target = match cli.target.clone().or_else(|| config_path("Target")) {
+                Some(target) => target,
+                None => match TempDir::new("serve") {
+                    Ok(dir) => {
+                        temporary = dir;
+                        temporary.path().to_owned()
+                    }
+                    Err(e) => {
+                        eprintln!("Could not create a temporary target: {}", e);
+                        std::process::exit(EXIT_CONFIG);
+                    }
+                },
+            };
+            let template = path(&cli.template, "Template", "--template", "the page template");
+
+            serve(
+                &root,
+                &target,
+                &template,
+                cli.port.unwrap_or(DEFAULT_PORT),
+                settings,
+            )
+        }
+        Command::Build | Command::Watch | Command::Help => {
+            let target = path(
+                &cli.target,
+                "Target",
+                "--target",
+                "where the HTML wikarden will be placed",
             );
-            std::process::exit(-1);
+            let template = path(&cli.template, "Template", "--template", "the page template");
+
+            if cli.command == Command::Watch {
+                watch(&root, &target, &template, settings, || true)
+            } else {
+                build(&root, &target, &template, settings)
+            }
+        }
+    };
+
+    std::process::exit(code);
+}
+
+// How pages are rendered, the same for every build
+#[derive(Clone, Copy)]
+struct Settings {
+    // How many pages are parsed at once
+    jobs: usize,
+    assets: AssetStrategy,
+}
+
+// The configuration, along with the directory its file is in
+struct Config {
+    conf: Confindent,
+    directory: PathBuf,
+}
+
+impl Config {
+    fn value(&self, key: &str) -> Option<&str> {
+        self.conf.child_value(key)
+    }
+
+    // Relative paths are from the configuration file, not the working
+    // directory, so it works wherever the generator is run from
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        self.value(key).map(|path| self.directory.join(path))
+    }
+}
+
+// Without `--config` the configuration is `generator.conf` in the working
+// directory, if there is one, so everything can come from the command line.
+fn load_config(path: Option<&Path>) -> Result<Option<Config>, String> {
+    let path = match path {
+        Some(path) => path,
+        None if Path::new("generator.conf").exists() => Path::new("generator.conf"),
+        None => return Ok(None),
+    };
+
+    match Confindent::from_file(path) {
+        Ok(conf) => Ok(Some(Config {
+            conf,
+            directory: path.parent().unwrap_or_else(|| Path::new("")).to_owned(),
+        })),
+        Err(e) => Err(format!(
+            "Could not parse the configuration file {}: {}",
+            path.display(),
+            e
+        )),
+    }
+}
+
+// Renders every page under root into target, unless nothing it's rendered from
+// changed since the last build. Gives back the exit code.
+fn build(root: &Path, target: &Path, template: &Path, settings: Settings) -> i32 {
+    let (root, target, doc) = match prepare(root, target, template) {
+        Ok(prepared) => prepared,
+        Err(code) => return code,
+    };
+
+    let root_directory = match Directory::index(&root) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Could not read the root directory: {}", e);
+            return EXIT_CONFIG;
+        }
+    };
+    let mds = root_directory.find_all_by_extension("md");
+    let mut sources = read_pages(&mds, settings.jobs);
+    let garden = Garden::new(&root_directory.base, &mds, &sources);
+
+    let hashes = manifest::hash_pages(&sources);
+    let last = Manifest::read(&target);
+    let mut manifest = Manifest {
+        template: std::fs::read(template)
+            .map(|bytes| manifest::hash(&bytes))
+            .unwrap_or_default(),
+        pages: BTreeMap::new(),
+    };
+    let relative = |path: &Path| path.strip_prefix(&root).unwrap_or(path).to_owned();
+
+    // Pages that were built from the same everything last time, and are still
+    // there, are kept as they are
+    let mut changed = BTreeMap::new();
+    for page in &mds {
+        let inputs = manifest::inputs(&garden, &hashes, page);
+        let built = target.join(relative(page)).with_extension("html");
+
+        match last.pages.get(&relative(page)) {
+            Some(last_inputs)
+                if last.template == manifest.template
+                    && last_inputs.hash == inputs.hash
+                    && last_inputs.dependencies == inputs.dependencies
+                    && built.exists() =>
+            {
+                manifest.pages.insert(relative(page), last_inputs.clone());
+            }
+            _ => {
+                changed.insert(page.to_path_buf(), inputs);
+            }
+        }
+    }
+
+    // Skipped pages were only needed for the garden
+    sources.retain(|page, _| changed.contains_key(page));
+
+    let only = changed.keys().cloned().collect();
+    let mut rendered = match render(
+        &root_directory,
+        &garden,
+        sources,
+        &doc,
+        &target,
+        Some(&only),
+        settings,
+    ) {
+        Ok(rendered) => rendered,
+        Err(e) => {
+            eprintln!("Could not build into {}: {}", target.display(), e);
+            return EXIT_CONFIG;
         }
     };
 
+    // Pages that didn't render aren't in the manifest so they're tried again
+    for (page, mut broken_links) in rendered.pages.drain(..) {
+        let mut inputs = changed.remove(&page).unwrap_or_default();
+        sort_broken_links(&mut broken_links);
+        inputs.broken_links = broken_links.iter().map(ToString::to_string).collect();
+        manifest.pages.insert(relative(&page), inputs);
+    }
+
+    if let Err(e) = manifest.write(&target) {
+        eprintln!("Could not write the build manifest: {}", e);
+    }
+
+    let broken_links: Vec<&String> = manifest
+        .pages
+        .values()
+        .flat_map(|inputs| &inputs.broken_links)
+        .collect();
+    let failed = report_failed(&rendered);
+    if report_broken(&broken_links) || failed {
+        EXIT_PROBLEMS
+    } else {
+        0
+    }
+}
+
+// Builds everything once, then looks for changes to root and the template
+// every so often and renders only the pages they affect, calling rendered
+// after each time. It stops if it can't get started or when rendered gives back
+// false, with the exit code for the last time it rendered.
+fn watch<F: FnMut() -> bool>(
+    root: &Path,
+    target: &Path,
+    template: &Path,
+    settings: Settings,
+    mut rendered: F,
+) -> i32 {
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    let (root, target, mut doc) = match prepare(root, target, template) {
+        Ok(prepared) => prepared,
+        Err(code) => return code,
+    };
+
+    // What's rendered here isn't kept track of, so the next build can't trust
+    // what the manifest says is there
+    if let Err(e) = Manifest::remove(&target) {
+        eprintln!("Could not remove the build manifest: {}", e);
+        return EXIT_CONFIG;
+    }
+
+    // How things looked after the last build, None before the first
+    let mut last: Option<(Stamps, Outline)> = None;
+    let mut code;
+
+    loop {
+        if last.is_some() {
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        let root_directory = match Directory::index(&root) {
+            Ok(dir) => dir,
+            Err(e) => {
+                eprintln!("Could not read the root directory: {}", e);
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+        };
+
+        let mut stamps = Stamps::new(&root_directory);
+        stamps.add(template);
+
+        let changes = match &last {
+            Some((old_stamps, _)) => old_stamps.changes(&stamps),
+            None => Changes::default(),
+        };
+        if last.is_some() && changes.is_empty() {
+            continue;
+        }
+
+        let template_changed = changes.contains(template);
+        if template_changed {
+            match Document::from_file(template, Options::default()) {
+                Ok(new_doc) => doc = new_doc,
+                Err(e) => eprintln!(
+                    "Could not read the template {}, keeping the old one: {:?}",
+                    template.display(),
+                    e
+                ),
+            }
+        }
+
+        let mds = root_directory.find_all_by_extension("md");
+        let sources = read_pages(&mds, settings.jobs);
+        let garden = Garden::new(&root_directory.base, &mds, &sources);
+        let outline = Outline::new(&garden);
+
+        // A new template changes every page
+        let only = match &last {
+            Some((_, old_outline)) if !template_changed => {
+                Some(watch::affected(old_outline, &outline, &changes))
+            }
+            _ => None,
+        };
+
+        for removed in &changes.removed {
+            remove_output(&root, &target, removed);
+        }
+
+        match render(
+            &root_directory,
+            &garden,
+            sources,
+            &doc,
+            &target,
+            only.as_ref(),
+            settings,
+        ) {
+            Ok(mut pages) => {
+                let broken = report_broken_links(
+                    pages.pages.drain(..).flat_map(|(_, links)| links).collect(),
+                );
+                let failed = report_failed(&pages);
+                let count = only.map(|only| only.len()).unwrap_or(mds.len());
+                println!("Rendered {} of {} pages", count, mds.len());
+
+                code = if broken || failed { EXIT_PROBLEMS } else { 0 };
+            }
+            Err(e) => {
+                eprintln!("Could not build into {}: {}", target.display(), e);
+                code = EXIT_CONFIG;
+            }
+        }
+
+        last = Some((stamps, outline));
+        if !rendered() {
+            return code;
+        }
+    }
+}
+
+// Watches, with the target served on localhost. Open pages are reloaded
+// whenever anything is rendered.
+fn serve(root: &Path, target: &Path, template: &Path, port: u16, settings: Settings) -> i32 {
+    if let Err(e) = std::fs::create_dir_all(target) {
+        eprintln!("Could not create {}: {}", target.display(), e);
+        return EXIT_CONFIG;
+    }
+
+    let server = match Server::bind(("127.0.0.1", port), target.to_owned()) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Could not listen on port {}: {}", port, e);
+            return EXIT_CONFIG;
+        }
+    };
+
+    match server.local_addr() {
+        Ok(addr) => println!("Serving {} at http://{}/", target.display(), addr),
+        Err(_) => println!("Serving {} on port {}", target.display(), port),
+    }
+
+    let generation = server.generation();
+    thread::spawn(move || server.run());
+
+    watch(root, target, template, settings, || {
+        generation.fetch_add(1, Ordering::SeqCst);
+        true
+    })
+}
+
+// Checks root and target are there, and reads the template. Root and target
+// are given back absolute. The error is the exit code.
+fn prepare(
+    root: &Path,
+    target: &Path,
+    template: &Path,
+) -> Result<(PathBuf, PathBuf, Document), i32> {
     let canon = match root.canonicalize() {
         Ok(canoned) => canoned,
         Err(e) => {
             eprintln!("Could not convert root path to absolute: {}", e);
-            std::process::exit(-1);
+            return Err(EXIT_CONFIG);
         }
     };
 
@@ -50,68 +459,334 @@ fn main() {
         Ok(canoned) => canoned,
         Err(e) => {
             eprintln!("Could not convert target path to absolute: {}", e);
-            std::process::exit(-1);
+            return Err(EXIT_CONFIG);
         }
     };
 
-    let doc =
-        Document::from_file(conf.child_value("Template").unwrap(), Options::default()).unwrap();
+    let doc = match Document::from_file(template, Options::default()) {
+        Ok(doc) => doc,
+        Err(e) => {
+            eprintln!(
+                "Could not read the template {}: {:?}",
+                template.display(),
+                e
+            );
+            return Err(EXIT_CONFIG);
+        }
+    };
 
-    let root_directory = Directory::index(canon).unwrap();
-    let mds = root_directory.find_all_by_extension("md");
+    Ok((canon, target_canon, doc))
+}
+
+// What came of rendering pages
+struct Rendered {
+    // Every page that was rendered, with the broken links on it
+    pages: Vec<(PathBuf, Vec<BrokenLink>)>,
+    // And the ones that weren't because they don't parse
+    failed: Vec<(PathBuf, ParseError)>,
+    // Or couldn't be read
+    unread: Vec<PathBuf>,
+}
+
+// Renders the pages into target from their sources, or with only, just those
+// pages, with as many at once as there are jobs. Every other file is placed in
+// target the way the settings say, unless it already is.
+fn render(
+    root_directory: &Directory,
+    garden: &Garden,
+    mut sources: HashMap<PathBuf, Source>,
+    doc: &Document,
+    target: &Path,
+    only: Option<&BTreeSet<PathBuf>>,
+    settings: Settings,
+) -> Result<Rendered, DirectoryError> {
+    // Walking the tree makes the directories and places everything that isn't
+    // a page. The pages are only found, they're rendered after.
+    let pages = RefCell::new(vec![]);
+    root_directory.clone_structure(target, settings.assets, |from, to| match from.extension() {
+        Some(ext) if ext == "md" => {
+            if only.map(|only| only.contains(from)) != Some(false) {
+                pages
+                    .borrow_mut()
+                    .push((from.to_owned(), to.with_extension("html")));
+            }
+            false
+        }
+        _ => true,
+    })?;
+
+    // Every broken interlink and page that doesn't parse, so one bad page
+    // doesn't stop the build. They're reported once everything else is done.
+    let mut rendered = Rendered {
+        pages: vec![],
+        failed: vec![],
+        unread: vec![],
+    };
+
+    // Sorted so everything happens in the same order every time
+    let mut found = pages.into_inner();
+    found.sort();
+    let mut pages = vec![];
+    let mut page_sources = vec![];
+    for (from, to) in found {
+        match sources.remove(&from) {
+            Some(source) => {
+                pages.push((from, to));
+                page_sources.push(source);
+            }
+            None => rendered.unread.push(from),
+        }
+    }
+
+    let to_render = pages
+        .iter()
+        .map(|(from, _)| from.as_path())
+        .zip(page_sources)
+        .collect();
+    let parsed = render_pages(to_render, garden, settings.jobs);
+
+    for ((from, to), page) in pages.iter().zip(parsed) {
+        let page = match page {
+            Ok(page) => page,
+            Err(e) => {
+                rendered.failed.push((from.to_owned(), e));
+                continue;
+            }
+        };
+
+        let mut doc = doc.clone();
+        doc.set("page_title", to.file_stem().unwrap().to_string_lossy());
+
+        let mknest = |nest: String| -> String {
+            if nest == "home" {
+                format!("<a href='home.html' id='nest'>entrance</a>")
+            } else {
+                format!("<a href='../{nest}.html' id='nest'>{nest}</a>", nest = nest)
+            }
+        };
+
+        match find_friends(root_directory, from) {
+            (Some(nest), friends) => {
+                doc.set("nest", mknest(nest));
+                doc.set("friends", friends);
+            }
+            (None, friends) => {
+                doc.set("nest", mknest("home".into()));
+                doc.set("friends", friends);
+            }
+        };
+
+        doc.set("title", page.title);
+        doc.set("backlinks", garden.backlinks_html(from));
+        doc.set("body", page.body);
+
+        // Every metadata key is available to the template, so
+        // `Author` becomes {meta_author}
+        for meta in page.metadata {
+            doc.set(
+                format!("meta_{}", meta.key.to_lowercase()),
+                html_escape(meta.value),
+            );
+        }
+
+        let mut file = File::create(to).unwrap();
+        file.write_all(doc.compile().as_bytes()).unwrap();
+        rendered.pages.push((from.to_owned(), page.broken_links));
+    }
+
+    Ok(rendered)
+}
+
+// Takes what a file that's gone from root was built into out of target
+fn remove_output(root: &Path, target: &Path, removed: &Path) {
+    let relative = match removed.strip_prefix(root) {
+        Ok(relative) => relative,
+        Err(_) => return,
+    };
+
+    let mut output = target.join(relative);
+    if relative.extension().map(|ext| ext == "md") == Some(true) {
+        output.set_extension("html");
+    }
+
+    match std::fs::remove_file(&output) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            eprintln!("Could not remove {}: {}", output.display(), e)
+        }
+        _ => (),
+    }
+}
+
+// Prints every broken link sorted by where it is. Gives back whether there
+// were any.
+fn report_broken_links(mut broken_links: Vec<BrokenLink>) -> bool {
+    sort_broken_links(&mut broken_links);
+    report_broken(&broken_links)
+}
+
+fn sort_broken_links(broken_links: &mut [BrokenLink]) {
+    broken_links.sort_by_key(|link| (link.file.clone(), link.position.line, link.position.column));
+}
+
+// Prints every page that couldn't be read, and why every page that didn't
+// parse didn't. Gives back whether there were any.
+fn report_failed(rendered: &Rendered) -> bool {
+    for path in &rendered.unread {
+        eprintln!("Could not read {}", path.display());
+    }
 
-    root_directory
-        .clone_structure(target_canon, |from, to| match from.extension() {
-            Some(ext) => {
-                if ext.to_string_lossy() == "md" {
-                    let mut to = to.to_owned();
-                    to.set_extension("html");
+    for (path, e) in &rendered.failed {
+        eprintln!("Could not parse {}: {}", path.display(), e);
+    }
 
-                    let mut from_no_ext = from.to_owned();
-                    from_no_ext.set_extension("");
+    !rendered.unread.is_empty() || !rendered.failed.is_empty()
+}
 
-                    let file_stem = to.file_stem().unwrap().to_str().unwrap();
+// Prints broken links in the order they're in
+fn report_broken<T: std::fmt::Display>(broken_links: &[T]) -> bool {
+    if broken_links.is_empty() {
+        return false;
+    }
 
-                    let mut from_dir = from.to_owned();
-                    from_dir.pop();
+    for link in broken_links {
+        eprintln!("{}", link);
+    }
+    eprintln!("{} broken interlinks", broken_links.len());
 
-                    let mut doc = doc.clone();
-                    doc.set("page_title", to.file_stem().unwrap().to_string_lossy());
+    true
+}
 
-                    let mknest = |nest: String| -> String {
-                        if nest == "home" {
-                            format!("<a href='home.html' id='nest'>entrance</a>")
-                        } else {
-                            format!("<a href='../{nest}.html' id='nest'>{nest}</a>", nest = nest)
-                        }
-                    };
+// Parses every page, reporting the ones that don't parse and any broken links,
+// then the pages that are cut off from the rest. Gives back the exit code,
+// which only the first two change; most gardens have pages that are meant to
+// be leaves.
+fn check_garden(root: &Path, entry_points: &[&str], json: bool, jobs: usize) -> i32 {
+    let root_directory = match Directory::index(root) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Could not read the root directory: {}", e);
+            return EXIT_CONFIG;
+        }
+    };
 
-                    match find_friends(&root_directory, from) {
-                        (Some(nest), friends) => {
-                            doc.set("nest", mknest(nest));
-                            doc.set("friends", friends);
-                        }
-                        (None, friends) => {
-                            doc.set("nest", mknest("home".into()));
-                            doc.set("friends", friends);
-                        }
-                    };
+    let mut mds = root_directory.find_all_by_extension("md");
+    mds.sort();
+    let mut sources = read_pages(&mds, jobs);
+    let garden = Garden::new(&root_directory.base, &mds, &sources);
+
+    let mut failed = false;
+    let mut pages = vec![];
+    for path in &mds {
+        match sources.remove(*path) {
+            Some(source) => pages.push((*path, source)),
+            None => {
+                eprintln!("Could not read {}", path.display());
+                failed = true;
+            }
+        }
+    }
 
-                    let (title, parsed) = parse_file(from, &mds);
-                    doc.set("title", title);
-                    doc.set("body", parsed);
+    let paths: Vec<&Path> = pages.iter().map(|(path, _)| *path).collect();
+    let mut broken_links = vec![];
+    for (path, page) in paths.into_iter().zip(render_pages(pages, &garden, jobs)) {
+        match page {
+            Ok(page) => broken_links.extend(page.broken_links),
+            Err(e) => {
+                eprintln!("Could not parse {}: {}", path.display(), e);
+                failed = true;
+            }
+        }
+    }
+    failed |= report_broken_links(broken_links);
+
+    let report = Report::new(&garden, entry_points);
+    if json {
+        print!("{}", report.json());
+    } else {
+        print!("{}", report);
+    }
+
+    if failed {
+        EXIT_PROBLEMS
+    } else {
+        0
+    }
+}
 
-                    let mut file = File::create(to).unwrap();
-                    file.write_all(doc.compile().as_bytes()).unwrap();
+// Formats every page under root in place, or with check, lists the ones that
+// would change. Gives back the exit code.
+fn format_pages(root: &Path, check: bool) -> i32 {
+    let root_directory = match Directory::index(root) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Could not read the root directory: {}", e);
+            return EXIT_CONFIG;
+        }
+    };
 
-                    false
-                } else {
-                    true
-                }
+    let mut mds = root_directory.find_all_by_extension("md");
+    mds.sort();
+
+    let mut failed = false;
+    for path in mds {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("Could not read {}: {}", path.display(), e);
+                failed = true;
+                continue;
+            }
+        };
+
+        let formatted = match format_source(&source) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("Could not parse {}: {}", path.display(), e);
+                failed = true;
+                continue;
             }
-            None => true,
-        })
-        .unwrap();
+        };
+
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            println!("{} is not formatted", path.display());
+            failed = true;
+        } else if let Err(e) = std::fs::write(path, formatted) {
+            eprintln!("Could not write {}: {}", path.display(), e);
+            failed = true;
+        }
+    }
+
+    if failed {
+        EXIT_PROBLEMS
+    } else {
+        0
+    }
+}
+
+fn print_graph(root: &Path, json: bool, external: bool, jobs: usize) -> i32 {
+    let root_directory = match Directory::index(root) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Could not read the root directory: {}", e);
+            return EXIT_CONFIG;
+        }
+    };
+
+    let mds = root_directory.find_all_by_extension("md");
+    let garden = Garden::new(&root_directory.base, &mds, &read_pages(&mds, jobs));
+    let graph = Graph::new(&garden, external);
+
+    if json {
+        print!("{}", graph.json());
+    } else {
+        print!("{}", graph.dot());
+    }
+
+    0
 }
 
 fn find_friends<'r>(root: &'r Directory, from: &Path) -> (Option<String>, String) {
@@ -213,3 +888,54 @@ fn make_children(current: &str, dir: &Directory) -> String {
 
     ret
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn config_paths_are_from_its_directory() {
+        let dir = TempDir::new("config").unwrap();
+        let file = dir.path().join("gardens/a/generator.conf");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, "Root pages\nTarget /srv/a\nEntry home\n").unwrap();
+
+        let config = load_config(Some(&file)).unwrap().unwrap();
+        assert_eq!(
+            config.path("Root"),
+            Some(dir.path().join("gardens/a/pages"))
+        );
+        assert_eq!(config.path("Target"), Some(PathBuf::from("/srv/a")));
+        assert_eq!(config.value("Entry"), Some("home"));
+        assert_eq!(config.path("Template"), None);
+    }
+
+    #[test]
+    fn pages_that_dont_parse_are_problems() {
+        let dir = TempDir::new("build").unwrap();
+        let (root, target, template) = (
+            dir.path().join("root"),
+            dir.path().join("target"),
+            dir.path().join("t.html"),
+        );
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(&template, "{body}").unwrap();
+        std::fs::write(root.join("home.md"), "Fine").unwrap();
+
+        let settings = Settings {
+            jobs: 2,
+            assets: AssetStrategy::Copy,
+        };
+        assert_eq!(build(&root, &target, &template, settings), 0);
+
+        std::fs::write(root.join("bad.md"), "*never closed").unwrap();
+        assert_eq!(build(&root, &target, &template, settings), EXIT_PROBLEMS);
+        // It wasn't rendered so it's tried, and fails, again
+        assert_eq!(build(&root, &target, &template, settings), EXIT_PROBLEMS);
+        assert_eq!(
+            watch(&root, &target, &template, settings, || false),
+            EXIT_PROBLEMS
+        );
+    }
+}
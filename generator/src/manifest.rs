@@ -0,0 +1,267 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{Garden, Source};
+
+/// Where in the target the manifest is kept
+pub const FILE_NAME: &str = ".generator-manifest";
+
+/// What every page was last rendered from, so a build can skip the pages
+/// whose inputs are all the same as last time. Paths are relative to the root.
+#[derive(Debug, Default, PartialEq)]
+pub struct Manifest {
+    pub template: u64,
+    pub pages: BTreeMap<PathBuf, Inputs>,
+}
+
+/// Everything a page's HTML comes from, besides the template
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Inputs {
+    /// Of the page's source
+    pub hash: u64,
+    /// Hashes of the pages it links to and that link to it, which give it its
+    /// hrefs and backlinks, and of the directories its navigation lists. A
+    /// directory's hash is of the names of the pages in it.
+    pub dependencies: BTreeMap<PathBuf, u64>,
+    /// The broken links on the page as they were reported, to report them again
+    /// when it's skipped
+    pub broken_links: Vec<String>,
+}
+
+impl Manifest {
+    /// An empty manifest if there isn't one or it can't be read, which means
+    /// every page is rendered
+    pub fn read(target: &Path) -> Self {
+        std::fs::read_to_string(target.join(FILE_NAME))
+            .ok()
+            .and_then(|raw| Self::parse(&raw))
+            .unwrap_or_default()
+    }
+
+    pub fn write(&self, target: &Path) -> std::io::Result<()> {
+        std::fs::write(target.join(FILE_NAME), self.to_string())
+    }
+
+    /// Forgets what was built, for when pages are rendered without keeping track
+    pub fn remove(target: &Path) -> std::io::Result<()> {
+        match std::fs::remove_file(target.join(FILE_NAME)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn parse(raw: &str) -> Option<Self> {
+        let mut manifest = Self::default();
+        let mut page: Option<&mut Inputs> = None;
+
+        for line in raw.lines() {
+            let (kind, rest) = line.split_once(' ')?;
+
+            match kind {
+                "template" => manifest.template = parse_hash(rest)?,
+                "page" => {
+                    let (hash, path) = rest.split_once(' ')?;
+                    let inputs = Inputs {
+                        hash: parse_hash(hash)?,
+                        ..Inputs::default()
+                    };
+                    page = Some(manifest.pages.entry(PathBuf::from(path)).or_insert(inputs));
+                }
+                "\tdepends" => {
+                    let (hash, path) = rest.split_once(' ')?;
+                    page.as_mut()?
+                        .dependencies
+                        .insert(PathBuf::from(path), parse_hash(hash)?);
+                }
+                "\tbroken" => page.as_mut()?.broken_links.push(unescape(rest)),
+                _ => return None,
+            }
+        }
+
+        Some(manifest)
+    }
+}
+
+// One thing to a line, with whatever's indented belonging to the page above
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "template {:016x}", self.template)?;
+
+        for (path, inputs) in &self.pages {
+            writeln!(f, "page {:016x} {}", inputs.hash, path.display())?;
+
+            for (dependency, hash) in &inputs.dependencies {
+                writeln!(f, "\tdepends {:016x} {}", hash, dependency.display())?;
+            }
+
+            for broken in &inputs.broken_links {
+                writeln!(f, "\tbroken {}", escape(broken))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 64-bit FNV-1a. It doesn't need to be strong, only the same from one build to
+/// the next, which the hasher in std doesn't promise.
+pub fn hash(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+/// The hash of every page that was read
+pub fn hash_pages(sources: &HashMap<PathBuf, Source>) -> HashMap<PathBuf, u64> {
+    sources
+        .iter()
+        .map(|(path, source)| (path.clone(), hash(source.text.as_bytes())))
+        .collect()
+}
+
+/// What the page is rendered from as things are now, save for its broken links
+/// which aren't known until it's rendered. Hashes are from [hash_pages].
+pub fn inputs(garden: &Garden, hashes: &HashMap<PathBuf, u64>, page: &Path) -> Inputs {
+    let relative = |path: &Path| path.strip_prefix(garden.root).unwrap_or(path).to_owned();
+    let mut dependencies = BTreeMap::new();
+
+    for link in &garden.links {
+        let other = if link.from == page {
+            &link.to
+        } else if link.to == page {
+            &link.from
+        } else {
+            continue;
+        };
+
+        if let Some(hash) = hashes.get(other) {
+            dependencies.insert(relative(other), *hash);
+        }
+    }
+
+    // Its friends are the pages next to it, or the ones in the directory
+    // named after it
+    let children = page.with_extension("");
+    for directory in page.parent().into_iter().chain(Some(children.as_path())) {
+        let mut names: Vec<&str> = garden
+            .files
+            .iter()
+            .filter(|file| file.parent() == Some(directory))
+            .filter_map(|file| file.file_name()?.to_str())
+            .collect();
+
+        if !names.is_empty() {
+            names.sort_unstable();
+            dependencies.insert(relative(directory), hash(names.join("\n").as_bytes()));
+        }
+    }
+
+    Inputs {
+        hash: hashes.get(page).copied().unwrap_or_default(),
+        dependencies,
+        broken_links: vec![],
+    }
+}
+
+fn parse_hash(raw: &str) -> Option<u64> {
+    u64::from_str_radix(raw, 16).ok()
+}
+
+// Broken links can go over more than one line
+fn escape(raw: &str) -> String {
+    raw.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(raw: &str) -> String {
+    let mut ret = String::with_capacity(raw.len());
+
+    let mut chars = raw.chars();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                chars.next();
+                ret.push('\n');
+            }
+            ('\\', Some('\\')) => {
+                chars.next();
+                ret.push('\\');
+            }
+            (ch, _) => ret.push(ch),
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Link;
+
+    #[test]
+    fn round_trips() {
+        let mut dependencies = BTreeMap::new();
+        dependencies.insert(PathBuf::from("inner"), 7);
+        dependencies.insert(PathBuf::from("two words.md"), u64::MAX);
+
+        let mut manifest = Manifest {
+            template: 42,
+            pages: BTreeMap::new(),
+        };
+        manifest.pages.insert(
+            PathBuf::from("inner.md"),
+            Inputs {
+                hash: 1,
+                dependencies,
+                broken_links: vec![String::from("a could be any of\n\tb\\c")],
+            },
+        );
+        manifest
+            .pages
+            .insert(PathBuf::from("home.md"), Inputs::default());
+
+        assert_eq!(Manifest::parse(&manifest.to_string()), Some(manifest));
+        assert_eq!(Manifest::parse("page nothex home.md"), None);
+    }
+
+    #[test]
+    fn depends_on_links_and_listings() {
+        let files = [
+            Path::new("/wiki/home.md"),
+            Path::new("/wiki/inner.md"),
+            Path::new("/wiki/inner/notes.md"),
+            Path::new("/wiki/lone.md"),
+        ];
+        let garden = Garden {
+            root: Path::new("/wiki"),
+            files: &files,
+            headings: HashMap::new(),
+            titles: HashMap::new(),
+            links: vec![Link {
+                from: PathBuf::from("/wiki/home.md"),
+                to: PathBuf::from("/wiki/inner.md"),
+                excerpt: String::new(),
+            }],
+            external_links: vec![],
+        };
+        let hashes = files
+            .iter()
+            .enumerate()
+            .map(|(idx, file)| (file.to_path_buf(), idx as u64))
+            .collect();
+
+        let inputs = inputs(&garden, &hashes, Path::new("/wiki/inner.md"));
+        assert_eq!(inputs.hash, 1);
+        assert_eq!(
+            inputs.dependencies.keys().collect::<Vec<_>>(),
+            vec![Path::new(""), Path::new("home.md"), Path::new("inner")]
+        );
+        assert_eq!(inputs.dependencies[Path::new("inner")], hash(b"notes.md"));
+    }
+}
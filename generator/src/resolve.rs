@@ -0,0 +1,146 @@
+use std::path::{Component, Path, PathBuf};
+
+/// What an interlink's location turned out to point at
+#[derive(Debug, PartialEq)]
+pub enum Resolution<'a> {
+    Found(&'a Path),
+    Missing,
+    /// More than one page fits and none of them is closer than the others
+    Ambiguous(Vec<&'a Path>),
+}
+
+/// Finds the page an interlink on `page` means. Every path has to be absolute.
+///
+/// - `{/dir/notes}` is `dir/notes.md` under root
+/// - `{./notes}` and `{../notes}` are relative to the directory of the page
+/// - `{notes}` and `{dir/notes}` are looked for in the page's directory first,
+///   then in every directory above it up to root. Failing that, any page whose
+///   path ends the same way will do if it's the only one.
+pub fn resolve<'a>(location: &str, page: &Path, root: &Path, files: &[&'a Path]) -> Resolution<'a> {
+    let file_name = format!("{}.md", location);
+    let page_dir = page.parent().unwrap_or(root);
+
+    let exactly = |path: PathBuf| -> Resolution<'a> {
+        let path = normalise(&path);
+
+        match files.iter().find(|file| **file == path) {
+            Some(file) => Resolution::Found(file),
+            None => Resolution::Missing,
+        }
+    };
+
+    if let Some(anchored) = file_name.strip_prefix('/') {
+        return exactly(root.join(anchored));
+    }
+
+    if file_name.starts_with("./") || file_name.starts_with("../") {
+        return exactly(page_dir.join(&file_name));
+    }
+
+    for dir in page_dir.ancestors().take_while(|dir| dir.starts_with(root)) {
+        if let Resolution::Found(file) = exactly(dir.join(&file_name)) {
+            return Resolution::Found(file);
+        }
+    }
+
+    let mut found: Vec<&Path> = files
+        .iter()
+        .filter(|file| file.ends_with(&file_name))
+        .copied()
+        .collect();
+
+    match found.len() {
+        0 => Resolution::Missing,
+        1 => Resolution::Found(found.remove(0)),
+        _ => Resolution::Ambiguous(found),
+    }
+}
+
+// Takes out the `.` and `..` in a path without going to the filesystem
+fn normalise(path: &Path) -> PathBuf {
+    let mut ret = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                ret.pop();
+            }
+            component => ret.push(component),
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FILES: &[&str] = &[
+        "/wiki/home.md",
+        "/wiki/todo.md",
+        "/wiki/one/todo.md",
+        "/wiki/one/notes.md",
+        "/wiki/one/deep/page.md",
+        "/wiki/two/notes.md",
+        "/wiki/two/page.md",
+    ];
+
+    fn resolve_from(location: &str, page: &str) -> Resolution<'static> {
+        let files: Vec<&Path> = FILES.iter().map(Path::new).collect();
+        resolve(location, Path::new(page), Path::new("/wiki"), &files)
+    }
+
+    #[test]
+    fn nearest_first() {
+        let found = |path| Resolution::Found(Path::new(path));
+
+        assert_eq!(
+            resolve_from("todo", "/wiki/one/page.md"),
+            found("/wiki/one/todo.md")
+        );
+        assert_eq!(
+            resolve_from("todo", "/wiki/two/page.md"),
+            found("/wiki/todo.md")
+        );
+        assert_eq!(
+            resolve_from("notes", "/wiki/one/deep/page.md"),
+            found("/wiki/one/notes.md")
+        );
+        assert_eq!(
+            resolve_from("two/page", "/wiki/one/notes.md"),
+            found("/wiki/two/page.md")
+        );
+    }
+
+    #[test]
+    fn anchored_and_relative() {
+        let found = |path| Resolution::Found(Path::new(path));
+
+        assert_eq!(
+            resolve_from("/todo", "/wiki/one/notes.md"),
+            found("/wiki/todo.md")
+        );
+        assert_eq!(
+            resolve_from("./todo", "/wiki/one/notes.md"),
+            found("/wiki/one/todo.md")
+        );
+        assert_eq!(
+            resolve_from("../../two/notes", "/wiki/one/deep/page.md"),
+            found("/wiki/two/notes.md")
+        );
+        assert_eq!(
+            resolve_from("/notes", "/wiki/one/notes.md"),
+            Resolution::Missing
+        );
+    }
+
+    #[test]
+    fn ambiguous_without_a_nearest() {
+        match resolve_from("notes", "/wiki/home.md") {
+            Resolution::Ambiguous(found) => assert_eq!(found.len(), 2),
+            res => panic!("expected an ambiguous link, got {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+// The live reload script asks here for the generation
+const RELOAD_PATH: &str = "/__reload";
+
+// Reloads the page once the generation is different from the first one it saw
+const RELOAD_SCRIPT: &str = r#"<script>
+(function () {
+	var seen = null;
+	setInterval(function () {
+		fetch("/__reload", { cache: "no-store" })
+			.then(function (response) { return response.text(); })
+			.then(function (generation) {
+				if (seen !== null && generation !== seen) {
+					location.reload();
+				}
+				seen = generation;
+			})
+			.catch(function () {});
+	}, 1000);
+})();
+</script>
+"#;
+
+/// Serves a built garden over HTTP. Every HTML page gets a script that
+/// reloads it when the generation goes up.
+pub struct Server {
+    listener: TcpListener,
+    root: PathBuf,
+    generation: Arc<AtomicUsize>,
+}
+
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(addr: A, root: PathBuf) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            root,
+            generation: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Add to it whenever pages are rendered so they're reloaded
+    pub fn generation(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.generation)
+    }
+
+    /// Answers every request on a thread of its own. Never returns.
+    pub fn run(self) {
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Could not accept a connection: {}", e);
+                    continue;
+                }
+            };
+
+            let root = self.root.clone();
+            let generation = Arc::clone(&self.generation);
+            thread::spawn(move || {
+                // Errors here are the browser going away, nothing to be done
+                let _ = respond(stream, &root, &generation);
+            });
+        }
+    }
+}
+
+fn respond(stream: TcpStream, root: &Path, generation: &AtomicUsize) -> io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // None of the headers matter but they have to be read before answering
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return write_response(&stream, "400 Bad Request", "text/plain", b"Bad request"),
+    };
+
+    if method != "GET" {
+        return write_response(
+            &stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"Only GET is allowed",
+        );
+    }
+
+    if target == RELOAD_PATH {
+        let generation = generation.load(Ordering::SeqCst).to_string();
+        return write_response(&stream, "200 OK", "text/plain", generation.as_bytes());
+    }
+
+    let file = match file_for(root, target) {
+        Some(file) => file,
+        None => return write_response(&stream, "404 Not Found", "text/plain", b"Not found"),
+    };
+
+    match std::fs::read(&file) {
+        Ok(body) if content_type(&file) == "text/html" => {
+            let html = inject_reload(&String::from_utf8_lossy(&body));
+            write_response(&stream, "200 OK", "text/html", html.as_bytes())
+        }
+        Ok(body) => write_response(&stream, "200 OK", content_type(&file), &body),
+        Err(_) => write_response(&stream, "404 Not Found", "text/plain", b"Not found"),
+    }
+}
+
+fn write_response(
+    mut stream: &TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    // Nothing is cached, it could be rendered again any moment
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// The file under root a request is for. The root itself is `home.html` and
+/// pages can be asked for without the `.html`. Anything that would lead out
+/// of root is None, like anything that isn't there.
+pub fn file_for(root: &Path, target: &str) -> Option<PathBuf> {
+    let path = target.split(&['?', '#'][..]).next()?;
+    let path = percent_decode(path);
+    let relative = Path::new(path.trim_start_matches('/'));
+
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let mut file = root.join(relative);
+    if file.is_dir() {
+        file.push("home.html");
+    }
+
+    if file.is_file() {
+        Some(file)
+    } else if file.extension().is_none() && file.with_extension("html").is_file() {
+        Some(file.with_extension("html"))
+    } else {
+        None
+    }
+}
+
+fn percent_decode(raw: &str) -> String {
+    let bytes = raw.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let hex = raw
+            .get(idx + 1..idx + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[idx], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                idx += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                idx += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn content_type(file: &Path) -> &'static str {
+    let extension = file
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "html" => "text/html",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "txt" | "md" => "text/plain",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff2" => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+// Right before the end of the body if there is one, otherwise at the end
+fn inject_reload(html: &str) -> String {
+    match html.rfind("</body>") {
+        Some(idx) => format!("{}{}{}", &html[..idx], RELOAD_SCRIPT, &html[idx..]),
+        None => format!("{}{}", html, RELOAD_SCRIPT),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fs::TempDir;
+
+    #[test]
+    fn requests_to_files() {
+        let dir = TempDir::new("serve").unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("inner")).unwrap();
+        for file in &["home.html", "style.css", "inner/some page.html"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+
+        assert_eq!(file_for(root, "/"), Some(root.join("home.html")));
+        assert_eq!(
+            file_for(root, "/style.css?v=2"),
+            Some(root.join("style.css"))
+        );
+        assert_eq!(
+            file_for(root, "/inner/some%20page"),
+            Some(root.join("inner/some page.html"))
+        );
+        assert_eq!(file_for(root, "/inner/../style.css"), None);
+        assert_eq!(file_for(root, "/nothing.html"), None);
+    }
+
+    #[test]
+    fn reload_script_goes_in_the_body() {
+        let html = inject_reload("<html><body><p>hi</p></body></html>");
+        assert!(html.starts_with("<html><body><p>hi</p><script>"));
+        assert!(html.ends_with("</script>\n</body></html>"));
+
+        assert!(inject_reload("<p>hi</p>").ends_with("</script>\n"));
+    }
+}
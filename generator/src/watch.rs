@@ -0,0 +1,288 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{fs::Directory, Garden, Link};
+
+/// When every file was last changed, and how big it was then, by its path
+#[derive(Debug, Default)]
+pub struct Stamps(HashMap<PathBuf, (Option<SystemTime>, u64)>);
+
+impl Stamps {
+    /// Every file under the directory, however deep
+    pub fn new(directory: &Directory) -> Self {
+        let mut stamps = Self::default();
+        for file in directory.all_files() {
+            stamps.add(file);
+        }
+
+        stamps
+    }
+
+    /// Files that can't be read are left out, as if they weren't there
+    pub fn add<P: AsRef<Path>>(&mut self, path: P) {
+        if let Ok(meta) = std::fs::metadata(path.as_ref()) {
+            let stamp = (meta.modified().ok(), meta.len());
+            self.0.insert(path.as_ref().to_owned(), stamp);
+        }
+    }
+
+    /// What happened between these stamps and the newer ones
+    pub fn changes(&self, newer: &Stamps) -> Changes {
+        let mut changes = Changes::default();
+
+        for (path, stamp) in &newer.0 {
+            match self.0.get(path) {
+                None => changes.created.push(path.clone()),
+                Some(old) if old != stamp => changes.modified.push(path.clone()),
+                Some(_) => (),
+            }
+        }
+
+        changes.removed = self
+            .0
+            .keys()
+            .filter(|path| !newer.0.contains_key(*path))
+            .cloned()
+            .collect();
+
+        changes.modified.sort();
+        changes.created.sort();
+        changes.removed.sort();
+        changes
+    }
+}
+
+/// Files that changed between two looks at them. A renamed file was removed
+/// from where it was and created where it is now.
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    pub modified: Vec<PathBuf>,
+    pub created: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.created.is_empty() && self.removed.is_empty()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.modified
+            .iter()
+            .chain(&self.created)
+            .chain(&self.removed)
+            .any(|changed| changed == path)
+    }
+}
+
+/// What the pages of a garden show of each other: their titles, where their
+/// links lead and which headers there are to link to. Unlike a [Garden] it
+/// doesn't borrow anything so it can be kept around until the next change.
+#[derive(Debug, Default)]
+pub struct Outline {
+    files: BTreeSet<PathBuf>,
+    titles: HashMap<PathBuf, String>,
+    headings: HashMap<PathBuf, Vec<String>>,
+    links: Vec<Link>,
+}
+
+impl Outline {
+    pub fn new(garden: &Garden) -> Self {
+        Self {
+            files: garden.files.iter().map(|file| file.to_path_buf()).collect(),
+            titles: garden
+                .files
+                .iter()
+                .map(|file| (file.to_path_buf(), garden.title(file).to_owned()))
+                .collect(),
+            headings: garden.headings.clone(),
+            links: garden.links.clone(),
+        }
+    }
+
+    // Where the page links to, which the page shows as hrefs
+    fn outgoing(&self, page: &Path) -> BTreeSet<&Path> {
+        self.links
+            .iter()
+            .filter(|link| link.from == page)
+            .map(|link| link.to.as_path())
+            .collect()
+    }
+
+    // What the page shows in its backlinks
+    fn incoming(&self, page: &Path) -> BTreeSet<(&Path, Option<&String>, &str)> {
+        self.links
+            .iter()
+            .filter(|link| link.to == page)
+            .map(|link| {
+                let title = self.titles.get(&link.from);
+                (link.from.as_path(), title, link.excerpt.as_str())
+            })
+            .collect()
+    }
+}
+
+/// The pages in `new` that have to be rendered again after the changes. That's
+/// the pages that changed and every page that shows something different now:
+///
+/// - its links lead somewhere else, or to a page with different headers
+/// - other pages link to it differently, or the ones that do have new titles
+/// - a page was created or removed next to it, or under the directory named
+///   after it, which changes its navigation
+pub fn affected(old: &Outline, new: &Outline, changes: &Changes) -> BTreeSet<PathBuf> {
+    let appeared: Vec<&Path> = changes
+        .created
+        .iter()
+        .chain(&changes.removed)
+        .filter(|path| path.extension().map(|ext| ext == "md") == Some(true))
+        .map(PathBuf::as_path)
+        .collect();
+
+    let navigation_changed = |page: &Path| {
+        let children = page.with_extension("");
+        appeared
+            .iter()
+            .filter_map(|path| path.parent())
+            .any(|dir| Some(dir) == page.parent() || dir == children)
+    };
+
+    let headings_changed = |page: &Path| {
+        new.outgoing(page)
+            .into_iter()
+            .any(|to| old.headings.get(to) != new.headings.get(to))
+    };
+
+    new.files
+        .iter()
+        .filter(|page| {
+            changes.modified.contains(page)
+                || changes.created.contains(page)
+                || !old.files.contains(*page)
+                || old.outgoing(page) != new.outgoing(page)
+                || old.incoming(page) != new.incoming(page)
+                || headings_changed(page)
+                || navigation_changed(page)
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn outline(pages: &[(&str, &str)], links: &[(&str, &str)]) -> Outline {
+        let mut outline = Outline::default();
+
+        for (path, title) in pages {
+            outline.files.insert(PathBuf::from(path));
+            outline
+                .titles
+                .insert(PathBuf::from(path), String::from(*title));
+        }
+
+        for (from, to) in links {
+            outline.links.push(Link {
+                from: PathBuf::from(from),
+                to: PathBuf::from(to),
+                excerpt: String::new(),
+            });
+        }
+
+        outline
+    }
+
+    fn paths(paths: &[&str]) -> BTreeSet<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn only_what_shows_the_change() {
+        let pages = [
+            ("/w/home.md", "Home"),
+            ("/w/notes.md", "Notes"),
+            ("/w/far/away.md", "Away"),
+            ("/w/far/off.md", "Off"),
+        ];
+        let links = [("/w/home.md", "/w/notes.md")];
+        let old = outline(&pages, &links);
+
+        // Nothing else shows the page's content
+        let changes = Changes {
+            modified: vec![PathBuf::from("/w/far/off.md")],
+            ..Changes::default()
+        };
+        assert_eq!(
+            affected(&old, &outline(&pages, &links), &changes),
+            paths(&["/w/far/off.md"])
+        );
+
+        // The page it links to lists it in its backlinks, with the new title
+        let mut renamed = pages;
+        renamed[0].1 = "Welcome";
+        let changes = Changes {
+            modified: vec![PathBuf::from("/w/home.md")],
+            ..Changes::default()
+        };
+        assert_eq!(
+            affected(&old, &outline(&renamed, &links), &changes),
+            paths(&["/w/home.md", "/w/notes.md"])
+        );
+    }
+
+    #[test]
+    fn created_pages_change_navigation_and_links() {
+        let old = outline(&[("/w/home.md", "Home"), ("/w/far/away.md", "Away")], &[]);
+        // `{todo}` on away.md leads somewhere now
+        let new = outline(
+            &[
+                ("/w/home.md", "Home"),
+                ("/w/far.md", "Far"),
+                ("/w/far/away.md", "Away"),
+                ("/w/far/todo.md", "Todo"),
+            ],
+            &[("/w/far/away.md", "/w/far/todo.md")],
+        );
+        let changes = Changes {
+            created: vec![PathBuf::from("/w/far.md"), PathBuf::from("/w/far/todo.md")],
+            ..Changes::default()
+        };
+
+        assert_eq!(
+            affected(&old, &new, &changes),
+            paths(&[
+                "/w/far.md",
+                "/w/far/away.md",
+                "/w/far/todo.md",
+                "/w/home.md"
+            ])
+        );
+    }
+
+    #[test]
+    fn stamps_tell_what_changed() {
+        let mut old = Stamps::default();
+        let mut new = Stamps::default();
+        let stamp = |len| (None, len);
+
+        old.0.insert(PathBuf::from("same"), stamp(1));
+        old.0.insert(PathBuf::from("edited"), stamp(1));
+        old.0.insert(PathBuf::from("gone"), stamp(1));
+        new.0.insert(PathBuf::from("same"), stamp(1));
+        new.0.insert(PathBuf::from("edited"), stamp(2));
+        new.0.insert(PathBuf::from("new"), stamp(1));
+
+        assert_eq!(
+            old.changes(&new),
+            Changes {
+                modified: vec![PathBuf::from("edited")],
+                created: vec![PathBuf::from("new")],
+                removed: vec![PathBuf::from("gone")],
+            }
+        );
+        assert!(new.changes(&new).is_empty());
+    }
+}
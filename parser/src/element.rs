@@ -1,34 +1,157 @@
-use core::panic;
+/// A range of bytes in the source text that an element was parsed from.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
 
 #[derive(Debug)]
 pub enum Block {
-    Header { level: u8, content: Vec<Inline> },
-    Paragraph { content: Vec<Inline> },
-    CodeBlock { language: String, content: String },
-    Image { src: String, alt: String },
+    Header {
+        level: u8,
+        content: Vec<Inline>,
+        span: Span,
+    },
+    Paragraph {
+        content: Vec<Inline>,
+        span: Span,
+    },
+    CodeBlock {
+        language: String,
+        content: String,
+        span: Span,
+    },
+    Image {
+        /// The link the image came from. Its location is the image's source.
+        link: Inline,
+        alt: String,
+        span: Span,
+    },
+    Quote {
+        content: Vec<Block>,
+        span: Span,
+    },
+    Table {
+        header: Option<TableRow>,
+        /// How each column is aligned, from the header separator. There may be
+        /// fewer of these than there are columns.
+        alignments: Vec<Alignment>,
+        rows: Vec<TableRow>,
+        span: Span,
+    },
+    UnorderedList {
+        items: Vec<ListItem>,
+        span: Span,
+    },
+    OrderedList {
+        /// The number the first item was given
+        start: usize,
+        items: Vec<ListItem>,
+        span: Span,
+    },
+}
+
+impl Block {
+    pub fn span(&self) -> Span {
+        match self {
+            Block::Header { span, .. }
+            | Block::Paragraph { span, .. }
+            | Block::CodeBlock { span, .. }
+            | Block::Image { span, .. }
+            | Block::Quote { span, .. }
+            | Block::Table { span, .. }
+            | Block::UnorderedList { span, .. }
+            | Block::OrderedList { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TableRow {
+    /// Every cell is its own run of inlines
+    pub cells: Vec<Vec<Inline>>,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug)]
+pub struct ListItem {
+    pub content: Vec<Inline>,
+    /// Lists nested under this item
+    pub children: Vec<Block>,
+    pub span: Span,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Inline {
-    SoftBreak,
-    Text(String),
-    Code(String),
+    SoftBreak(Span),
+    Text(String, Span),
+    Code(String, Span),
     Italic {
         content: Vec<Inline>,
+        span: Span,
     },
     Bold {
         content: Vec<Inline>,
+        span: Span,
     },
     AbsoluteLink {
         name: Option<String>,
         location: String,
+        span: Span,
     },
     ReferenceLink {
         name: String,
+        /// The name of the `[Reference]: url` line the location came from
+        reference: String,
         location: String,
+        span: Span,
     },
     InterLink {
         name: String,
         location: String,
+        span: Span,
+    },
+    /// Refers to the footnote with this name in [crate::Parser::footnotes]
+    FootnoteReference {
+        name: String,
+        span: Span,
     },
 }
+
+impl Inline {
+    pub fn span(&self) -> Span {
+        match self {
+            Inline::SoftBreak(span) | Inline::Text(_, span) | Inline::Code(_, span) => *span,
+            Inline::Italic { span, .. }
+            | Inline::Bold { span, .. }
+            | Inline::AbsoluteLink { span, .. }
+            | Inline::ReferenceLink { span, .. }
+            | Inline::InterLink { span, .. }
+            | Inline::FootnoteReference { span, .. } => *span,
+        }
+    }
+
+    /// Where a link points. Anything that isn't a link has no location.
+    pub fn location(&self) -> Option<&str> {
+        match self {
+            Inline::AbsoluteLink { location, .. }
+            | Inline::ReferenceLink { location, .. }
+            | Inline::InterLink { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+}
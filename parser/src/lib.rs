@@ -1,63 +1,221 @@
 pub mod element;
+pub mod print;
+pub mod visit;
 
 use std::{collections::HashMap, fmt, str::FromStr};
 
-use element::{Block, Inline};
+use element::{Alignment, Block, Inline, ListItem, Span, TableRow};
+use visit::{Visitor, VisitorMut};
 
 use thiserror::Error;
 
 pub struct Parser {
     pub blocks: Vec<Block>,
+    /// The `Key Value` lines of a ```` ```metadata ```` block at the very
+    /// start of the page, in the order they were written. That block doesn't
+    /// end up in `blocks`.
+    pub metadata: Vec<Meta>,
+    /// Footnote text by name, from lines like `[^name]: text`
+    pub footnotes: HashMap<String, Vec<Inline>>,
+    /// Link locations by name, from lines like `[Name]: url`
+    pub references: HashMap<String, String>,
+}
+
+/// A line of the metadata block. Like in confindent, the lines indented under
+/// it are its children.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Meta {
+    pub key: String,
+    pub value: String,
+    pub children: Vec<Meta>,
 }
 
 impl Parser {
-    // parses into blocks and generates a map of link references
-    fn first_pass<S: AsRef<str>>(
-        raw: S,
-    ) -> Result<(Vec<Block>, HashMap<String, String>), ParseError> {
-        let mut blocks = vec![];
-        let mut linkrefs = HashMap::new();
+    /// The value of the first unindented metadata line with this key
+    pub fn meta(&self, key: &str) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|meta| meta.key == key)
+            .map(|meta| meta.value.as_str())
+    }
+
+    // parses into blocks and collects link references and footnotes
+    fn first_pass<S: AsRef<str>>(raw: S) -> Result<(Vec<Block>, Definitions), ParseError> {
+        let raw = raw.as_ref();
+        let mut defs = Definitions::default();
+
+        let blocks = Parser::parse_lines(raw, lines_with_offsets(raw), &mut defs)?;
+
+        Ok((blocks, defs))
+    }
+
+    // Takes the metadata block off the front of the page, if there is one
+    fn take_metadata(blocks: &mut Vec<Block>) -> Vec<Meta> {
+        let content = match blocks.first() {
+            Some(Block::CodeBlock {
+                language, content, ..
+            }) if language == "metadata" => content.clone(),
+            _ => return vec![],
+        };
+        blocks.remove(0);
+
+        let mut metadata: Vec<Meta> = vec![];
+        // How far in the last line at every depth was
+        let mut indents: Vec<usize> = vec![];
+
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            let trimmed = line.trim();
+            let indent = line.len() - line.trim_start().len();
+
+            // Like confindent, the key is the first word and the value is the rest
+            let (key, value) = match trimmed.split_once(char::is_whitespace) {
+                Some((key, value)) => (key.to_owned(), value.trim_start().to_owned()),
+                None => (trimmed.to_owned(), String::new()),
+            };
+
+            while indents.last() >= Some(&indent) {
+                indents.pop();
+            }
+
+            let mut siblings = &mut metadata;
+            for _ in 0..indents.len() {
+                siblings = &mut siblings.last_mut().unwrap().children;
+            }
+
+            siblings.push(Meta {
+                key,
+                value,
+                children: vec![],
+            });
+            indents.push(indent);
+        }
+
+        metadata
+    }
 
-        let lines = raw.as_ref().lines();
+    // Turns lines, paired with the byte offset they start at in raw, into
+    // blocks. The lines don't have to be all of raw; quotes pass in their own
+    // lines with the `>` taken off.
+    fn parse_lines<'a, I>(
+        raw: &'a str,
+        lines: I,
+        defs: &mut Definitions,
+    ) -> Result<Vec<Block>, ParseError>
+    where
+        I: IntoIterator<Item = (usize, &'a str)>,
+    {
+        let mut blocks = vec![];
 
         let mut last_line_paragraph = false;
-        let mut code_block_active = false;
-        for line in lines {
+        let mut code_block_start: Option<usize> = None;
+        // The indentation of every level of the list we're in, outermost first
+        let mut list_indents: Vec<usize> = vec![];
+        let mut lines = lines.into_iter().peekable();
+        while let Some((start, line)) = lines.next() {
+            let line_span = Span::new(start, start + line.len());
+
             // If a code block is active...
-            if code_block_active {
-                if line == "```" {
-                    // Make sure we can find the end
-                    code_block_active = false;
-                    continue;
-                } else {
-                    // And add to the block
-                    if let Some(Block::CodeBlock { content, .. }) = blocks.last_mut() {
+            if code_block_start.is_some() {
+                if let Some(Block::CodeBlock { content, span, .. }) = blocks.last_mut() {
+                    span.end = line_span.end;
+
+                    if line == "```" {
+                        // Make sure we can find the end
+                        code_block_start = None;
+                        continue;
+                    } else {
+                        // And add to the block
                         if !line.is_empty() {
                             content.push_str(line);
                         }
                         content.push('\n');
                         continue;
-                    } else {
-                        // This shouldn't be possible
-                        unreachable!("A code block is active but the last element was not a code block, how did this happen")
                     }
+                } else {
+                    // This shouldn't be possible
+                    unreachable!("A code block is active but the last element was not a code block, how did this happen")
                 }
             }
 
             // Filter out empty lines
             if line.is_empty() {
                 last_line_paragraph = false;
+                list_indents.clear();
                 continue;
             }
 
+            // Quotes run for as long as the lines start with `>`
+            if let Some(first) = quote_line(start, line) {
+                last_line_paragraph = false;
+                list_indents.clear();
+
+                let mut quoted = vec![first];
+                let mut end = line_span.end;
+                while let Some(quote) = lines.peek().and_then(|(s, l)| quote_line(*s, l)) {
+                    end = quote.0 + quote.1.len();
+                    quoted.push(quote);
+                    lines.next();
+                }
+
+                blocks.push(Block::Quote {
+                    content: Parser::parse_lines(raw, quoted, defs)?,
+                    span: Span::new(start, end),
+                });
+                continue;
+            }
+
+            // Tables are every line, from here on, that starts with a `|`
+            if line.starts_with('|') {
+                last_line_paragraph = false;
+                list_indents.clear();
+
+                let mut rows = vec![(start, line)];
+                while let Some((start, line)) = lines.next_if(|(_, l)| l.starts_with('|')) {
+                    rows.push((start, line));
+                }
+
+                blocks.push(Parser::table(rows));
+                continue;
+            }
+
+            // Lists only continue for as long as every line is part of them
+            let in_list = !list_indents.is_empty();
+            if in_list {
+                if let Some(item) = ListLine::parse(line) {
+                    Parser::push_list_item(&mut blocks, &mut list_indents, item, start, line_span);
+                    continue;
+                }
+
+                // Indented lines carry on the last item
+                if line.starts_with(char::is_whitespace) {
+                    if let Some(list) = blocks.last_mut() {
+                        let item = Parser::last_list_item(list);
+                        let text = line.trim_start();
+
+                        item.content
+                            .push(Inline::SoftBreak(Span::new(item.span.end, line_span.start)));
+                        item.content.push(Inline::Text(
+                            text.to_owned(),
+                            Span::new(line_span.end - text.len(), line_span.end),
+                        ));
+
+                        Parser::extend_list(list, line_span.end);
+                        continue;
+                    }
+                }
+
+                list_indents.clear();
+            }
+
             // We already passed where we find the end, so this is the beginning
-            if line.starts_with("```") {
+            if let Some(language) = line.strip_prefix("```") {
                 last_line_paragraph = false;
-                code_block_active = true;
+                code_block_start = Some(start);
 
                 blocks.push(Block::CodeBlock {
-                    language: line.strip_prefix("```").unwrap().to_owned(),
+                    language: language.to_owned(),
                     content: String::new(),
+                    span: line_span,
                 });
 
                 continue;
@@ -65,171 +223,505 @@ impl Parser {
 
             // Lines starts `[`, assume it's the link for a reference...
             if line.starts_with('[') {
-                match line.split_once("]: ") {
-                    // But don't just unwrap, check, too
-                    None => (),
-                    Some((ref_name, ref_link)) => {
+                // But don't just unwrap, check, too
+                if let Some((ref_name, ref_link)) = line.split_once("]: ") {
+                    last_line_paragraph = false;
+
+                    // ...unless it's `[^name]: text`, which is a footnote
+                    match ref_name[1..].strip_prefix('^') {
+                        Some(note_name) => {
+                            let text_start = start + ref_name.len() + 3;
+                            defs.footnotes.insert(
+                                note_name.to_owned(),
+                                vec![Inline::Text(
+                                    ref_link.to_owned(),
+                                    Span::new(text_start, line_span.end),
+                                )],
+                            );
+                        }
+                        None => {
+                            defs.linkrefs
+                                .insert(ref_name[1..].to_owned(), ref_link.to_owned());
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            // Find headers and make sure they're headers of 4 or less levels
+            if line.starts_with('#') {
+                if let Some((header, text)) = line.split_once(' ') {
+                    if header.replacen('#', "", 4).is_empty() {
                         last_line_paragraph = false;
-                        linkrefs.insert(ref_name[1..].to_owned(), ref_link.to_owned());
+
+                        let text_start = start + header.len() + 1;
+                        blocks.push(Block::Header {
+                            level: header.len() as u8,
+                            content: vec![Inline::Text(
+                                text.to_owned(),
+                                Span::new(text_start, line_span.end),
+                            )],
+                            span: line_span,
+                        });
                         continue;
                     }
                 }
             }
 
-            // Find headers and make sure they're headers of 4 or less levels
-            if line.starts_with("#") {
-                match line.split_once(" ") {
-                    Some((header, text)) => {
-                        if header.replacen('#', "", 4).is_empty() {
-                            last_line_paragraph = false;
-
-                            blocks.push(Block::Header {
-                                level: header.len() as u8,
-                                content: vec![Inline::Text(text.to_owned())],
-                            });
-                            continue;
-                        }
+            // Lists can start anywhere, even right after a paragraph
+            if let Some(item) = ListLine::parse(line) {
+                last_line_paragraph = false;
+                Parser::push_list_item(&mut blocks, &mut list_indents, item, start, line_span);
+                continue;
+            }
+
+            // Alt text turns the link on the line above it into an image
+            if let Some(alt) = line.strip_prefix('^') {
+                let dangling = ParseError::DanglingAlt {
+                    position: Position::from_offset(raw, start),
+                    snippet: snippet(line),
+                };
+
+                if !last_line_paragraph {
+                    return Err(dangling);
+                }
+
+                let (content, span) = match blocks.last_mut() {
+                    Some(Block::Paragraph { content, span }) => (content, span),
+                    // This shouldn't be able to happen
+                    _ => unreachable!(
+                        "last_line_was_paragraph but the last line was not, what's gone wrong?"
+                    ),
+                };
+
+                let (link, link_span) = match content.last() {
+                    Some(Inline::Text(txt, span)) if Parser::is_lone_link(txt) => {
+                        (txt.clone(), *span)
+                    }
+                    _ => return Err(dangling),
+                };
+
+                // Take the link, and the break before it, out of the paragraph
+                content.pop();
+                if let Some(Inline::SoftBreak(_)) = content.last() {
+                    content.pop();
+                }
+
+                match content.last() {
+                    Some(inline) => span.end = inline.span().end,
+                    None => {
+                        blocks.pop();
                     }
-                    _ => (),
                 }
+
+                blocks.push(Block::Image {
+                    // The second pass parses this into the link it really is
+                    link: Inline::Text(link, link_span),
+                    alt: alt.trim_start().to_owned(),
+                    span: Span::new(link_span.start, line_span.end),
+                });
+
+                last_line_paragraph = false;
+                continue;
             }
 
             // If we hit nothing else, we're a paragraph
             if !last_line_paragraph {
                 blocks.push(Block::Paragraph {
-                    content: vec![Inline::Text(line.to_owned())],
+                    content: vec![Inline::Text(line.to_owned(), line_span)],
+                    span: line_span,
                 });
+            } else if let Some(Block::Paragraph { content, span }) = blocks.last_mut() {
+                content.push(Inline::SoftBreak(Span::new(span.end, line_span.start)));
+                content.push(Inline::Text(line.to_owned(), line_span));
+                span.end = line_span.end;
             } else {
-                if let Some(Block::Paragraph { content }) = blocks.last() {
-                    match line.strip_prefix("^") {
-                        Some(alt) => {
-                            let link = if let Some(Block::Paragraph { content }) = blocks.last_mut()
-                            {
-                                match content.pop().unwrap() {
-                                    Inline::Text(txt) => txt,
-                                    _ => panic!(),
-                                }
-                            } else {
-                                panic!()
-                            };
-
-                            blocks.push(Block::Image {
-                                src: link,
-                                alt: alt.trim_start().to_owned(),
-                            })
-                        }
-                        None => {
-                            if let Some(Block::Paragraph { content }) = blocks.last_mut() {
-                                content.push(Inline::SoftBreak);
-                                content.push(Inline::Text(line.to_owned()));
-                            }
+                // This shouldn't be able to happen
+                unreachable!(
+                    "last_line_was_paragraph but the last line was not, what's gone wrong?"
+                )
+            }
+            last_line_paragraph = true;
+        }
+
+        if let Some(start) = code_block_start {
+            return Err(ParseError::UnclosedCodeBlock {
+                position: Position::from_offset(raw, start),
+                snippet: snippet(&raw[start..]),
+            });
+        }
+
+        Ok(blocks)
+    }
+
+    // The second row is the header separator, if there is one. If it's the
+    // first row instead then there are alignments but no header.
+    fn table(lines: Vec<(usize, &str)>) -> Block {
+        let span = Span::new(lines[0].0, {
+            let (start, line) = lines[lines.len() - 1];
+            start + line.len()
+        });
+
+        let separator_at = lines
+            .iter()
+            .take(2)
+            .position(|(_, line)| Parser::table_alignments(line).is_some());
+
+        let mut header = None;
+        let mut alignments = vec![];
+        let mut rows = vec![];
+        for (idx, (start, line)) in lines.into_iter().enumerate() {
+            match separator_at {
+                Some(sep) if sep == idx => {
+                    alignments = Parser::table_alignments(line).unwrap_or_default()
+                }
+                Some(1) if idx == 0 => header = Some(Parser::table_row(start, line)),
+                _ => rows.push(Parser::table_row(start, line)),
+            }
+        }
+
+        Block::Table {
+            header,
+            alignments,
+            rows,
+            span,
+        }
+    }
+
+    fn table_row(start: usize, line: &str) -> TableRow {
+        let cells = split_cells(line)
+            .into_iter()
+            .map(|(offset, cell)| {
+                let start = start + offset;
+                vec![Inline::Text(
+                    cell.to_owned(),
+                    Span::new(start, start + cell.len()),
+                )]
+            })
+            .collect();
+
+        TableRow {
+            cells,
+            span: Span::new(start, start + line.len()),
+        }
+    }
+
+    // A separator row looks like `| :-- | :-: | --: |`
+    fn table_alignments(line: &str) -> Option<Vec<Alignment>> {
+        split_cells(line)
+            .into_iter()
+            .map(|(_, cell)| Parser::cell_alignment(cell))
+            .collect()
+    }
+
+    fn cell_alignment(cell: &str) -> Option<Alignment> {
+        let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+        if dashes.is_empty() || !dashes.chars().all(|ch| ch == '-') {
+            return None;
+        }
+
+        Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+            (true, true) => Alignment::Center,
+            (true, false) => Alignment::Left,
+            (false, true) => Alignment::Right,
+            (false, false) => Alignment::None,
+        })
+    }
+
+    fn push_list_item(
+        blocks: &mut Vec<Block>,
+        list_indents: &mut Vec<usize>,
+        line: ListLine,
+        start: usize,
+        line_span: Span,
+    ) {
+        let text_start = start + line.text_offset;
+        let item = ListItem {
+            content: vec![Inline::Text(
+                line.text.to_owned(),
+                Span::new(text_start, line_span.end),
+            )],
+            children: vec![],
+            span: line_span,
+        };
+
+        // Step out of every list that's indented further than we are
+        let mut stepped_out = false;
+        while list_indents.len() > 1 && list_indents.last() > Some(&line.indent) {
+            list_indents.pop();
+            stepped_out = true;
+        }
+
+        let last_indent = match list_indents.last() {
+            Some(indent) => *indent,
+            None => {
+                // Nothing's open so we start a new list
+                list_indents.push(line.indent);
+                blocks.push(line.marker.list(item, line_span));
+                return;
+            }
+        };
+
+        // Still deeper than the list we're back in, so it goes on with the list
+        // we just stepped out of rather than starting another one beside it
+        let last_indent = if stepped_out && line.indent > last_indent {
+            list_indents.push(line.indent);
+            line.indent
+        } else {
+            last_indent
+        };
+
+        let depth = list_indents.len() - 1;
+        if line.indent > last_indent {
+            // Deeper than before so we nest under the last item
+            list_indents.push(line.indent);
+
+            let list = Parser::nested_list(blocks.last_mut().unwrap(), depth);
+            if let Block::UnorderedList { items, .. } | Block::OrderedList { items, .. } = list {
+                if let Some(parent) = items.last_mut() {
+                    parent.children.push(line.marker.list(item, line_span));
+                }
+            }
+        } else {
+            let list = Parser::nested_list(blocks.last_mut().unwrap(), depth);
+            match list {
+                Block::UnorderedList { items, .. } if line.marker == Marker::Unordered => {
+                    items.push(item)
+                }
+                Block::OrderedList { items, .. } if line.marker != Marker::Unordered => {
+                    items.push(item)
+                }
+                // Switching between ordered and unordered makes a new list
+                _ if depth == 0 => {
+                    blocks.push(line.marker.list(item, line_span));
+                    return;
+                }
+                _ => {
+                    let parent = Parser::nested_list(blocks.last_mut().unwrap(), depth - 1);
+                    if let Block::UnorderedList { items, .. } | Block::OrderedList { items, .. } =
+                        parent
+                    {
+                        if let Some(parent) = items.last_mut() {
+                            parent.children.push(line.marker.list(item, line_span));
                         }
                     }
+                }
+            }
+        }
+
+        Parser::extend_list(blocks.last_mut().unwrap(), line_span.end);
+    }
+
+    // Walks down to the list nested `depth` levels below this one, always
+    // following the last item
+    fn nested_list(list: &mut Block, depth: usize) -> &mut Block {
+        if depth == 0 {
+            return list;
+        }
+
+        match list {
+            Block::UnorderedList { items, .. } | Block::OrderedList { items, .. } => {
+                match items.last_mut().and_then(|item| item.children.last_mut()) {
+                    Some(child) => Parser::nested_list(child, depth - 1),
+                    None => unreachable!("list_indents is deeper than the list is"),
+                }
+            }
+            _ => unreachable!("nested_list was called on something that isn't a list"),
+        }
+    }
+
+    // The item the last line of the list belongs to
+    fn last_list_item(list: &mut Block) -> &mut ListItem {
+        match list {
+            Block::UnorderedList { items, .. } | Block::OrderedList { items, .. } => {
+                let item = items.last_mut().unwrap();
+
+                if item.children.is_empty() {
+                    item
                 } else {
-                    // This shouldn't be able to happen
-                    unreachable!(
-                        "last_line_was_paragraph but the last line was not, what's gone wrong?"
-                    )
+                    Parser::last_list_item(item.children.last_mut().unwrap())
                 }
             }
-            last_line_paragraph = true;
+            _ => unreachable!("last_list_item was called on something that isn't a list"),
         }
+    }
+
+    // Stretch the list, and its last items all the way down, to end at `end`
+    fn extend_list(list: &mut Block, end: usize) {
+        if let Block::UnorderedList { items, span } | Block::OrderedList { items, span, .. } = list
+        {
+            span.end = end;
 
-        Ok((blocks, linkrefs))
+            if let Some(item) = items.last_mut() {
+                item.span.end = end;
+
+                if let Some(child) = item.children.last_mut() {
+                    Parser::extend_list(child, end);
+                }
+            }
+        }
     }
 
-    fn second_pass(
-        mut blocks: Vec<Block>,
-        linkrefs: HashMap<String, String>,
-    ) -> Result<Vec<Block>, ParseError> {
+    fn second_pass(mut blocks: Vec<Block>, ctx: &Context) -> Result<Vec<Block>, ParseError> {
         for block in blocks.iter_mut() {
-            match block {
-                Block::Header { content, .. } => {
-                    *content = Parser::parse_inlines(content, &linkrefs)
-                }
-                Block::Paragraph { content } => {
-                    *content = Parser::parse_inlines(content, &linkrefs)
-                }
-                Block::Image { src, .. } => {
-                    let link_inline = &Parser::do_links(&src, &linkrefs)[1];
-                    let location = match link_inline {
-                        Inline::InterLink { location, .. } => location,
-                        Inline::ReferenceLink { location, .. } => location,
-                        _ => panic!(),
-                    };
-                    *src = location.clone();
+            Parser::parse_block(block, ctx)?;
+        }
+
+        Ok(blocks)
+    }
+
+    fn parse_block(block: &mut Block, ctx: &Context) -> Result<(), ParseError> {
+        match block {
+            Block::Header { content, .. } => *content = Parser::parse_inlines(content, ctx)?,
+            Block::Paragraph { content, .. } => *content = Parser::parse_inlines(content, ctx)?,
+            Block::Quote { content, .. } => {
+                for block in content.iter_mut() {
+                    Parser::parse_block(block, ctx)?;
+                }
+            }
+            Block::Table { header, rows, .. } => {
+                for row in header.iter_mut().chain(rows.iter_mut()) {
+                    for cell in row.cells.iter_mut() {
+                        *cell = Parser::parse_inlines(cell, ctx)?;
+                    }
                 }
-                _ => (),
             }
+            Block::UnorderedList { items, .. } | Block::OrderedList { items, .. } => {
+                for item in items {
+                    item.content = Parser::parse_inlines(&item.content, ctx)?;
+
+                    for child in item.children.iter_mut() {
+                        Parser::parse_block(child, ctx)?;
+                    }
+                }
+            }
+            Block::Image { link, span, .. } => {
+                let raw = match link {
+                    Inline::Text(raw, link_span) => Parser::do_links(raw, link_span.start, ctx)?,
+                    _ => return Ok(()),
+                };
+
+                match raw.into_iter().find(|inline| inline.location().is_some()) {
+                    Some(parsed) => *link = parsed,
+                    // is_lone_link let it through but it wasn't really a link
+                    None => {
+                        return Err(ParseError::DanglingAlt {
+                            position: ctx.position(span.start),
+                            snippet: snippet(&ctx.source[span.start..]),
+                        })
+                    }
+                }
+            }
+            _ => (),
         }
 
-        Ok(blocks)
+        Ok(())
     }
 
-    fn parse_inlines(inlines: &Vec<Inline>, linkrefs: &HashMap<String, String>) -> Vec<Inline> {
+    // A line that's nothing but a link, which is what an image's source is
+    fn is_lone_link(line: &str) -> bool {
+        let line = line.trim();
+        line.starts_with('{') && line.ends_with('}')
+    }
+
+    fn parse_inlines(inlines: &[Inline], ctx: &Context) -> Result<Vec<Inline>, ParseError> {
         let mut ret = vec![];
 
         for inline in inlines {
             match inline {
-                Inline::SoftBreak => ret.push(Inline::SoftBreak),
-                Inline::Text(txt) => ret.extend_from_slice(&Parser::parse_inline(txt, &linkrefs)),
+                Inline::SoftBreak(span) => ret.push(Inline::SoftBreak(*span)),
+                Inline::Text(txt, span) => {
+                    ret.extend_from_slice(&Parser::parse_inline(txt, span.start, ctx)?)
+                }
                 _ => (),
             }
         }
 
-        ret
+        Ok(ret)
     }
 
-    fn parse_inline<S: AsRef<str>>(raw: S, linkrefs: &HashMap<String, String>) -> Vec<Inline> {
+    // offset is where raw starts in the source
+    fn parse_inline<S: AsRef<str>>(
+        raw: S,
+        offset: usize,
+        ctx: &Context,
+    ) -> Result<Vec<Inline>, ParseError> {
         let raw = raw.as_ref();
 
+        // Tokens are paired with the byte offset they start at in the source
         let mut tokens = vec![];
         let mut current = String::new();
+        let mut current_start = offset;
 
         // Special flag for code
         let mut code_active = false;
 
-        let mut chars = raw.chars().peekable();
+        let mut chars = raw.char_indices().peekable();
         loop {
             match chars.next() {
-                Some('`') => {
+                // Escapes stay in the text, with what they escape, so nothing
+                // here sees them. do_links unescapes the text at the end.
+                Some((_, '\\')) if !code_active => {
+                    current.push('\\');
+                    if let Some((_, ch)) = chars.next_if(|(_, ch)| ESCAPABLE.contains(ch)) {
+                        current.push(ch);
+                    }
+                }
+                Some((idx, '`')) => {
+                    let idx = offset + idx;
+
                     if code_active {
                         match tokens.last_mut() {
-                            Some(Token::Code(st)) => *st = current.clone(),
+                            Some((_, Token::Code(st))) => *st = current.clone(),
                             _ => unreachable!(),
                         }
                         current.clear();
                         code_active = false
                     } else {
-                        tokens.push(Token::Text(current.clone()));
-                        tokens.push(Token::Code(String::new()));
+                        tokens.push((current_start, Token::Text(current.clone())));
+                        tokens.push((idx, Token::Code(String::new())));
                         current.clear();
                         code_active = true
                     }
+                    current_start = idx + 1;
                 }
-                Some(ch) if code_active => current.push(ch),
+                Some((_, ch)) if code_active => current.push(ch),
                 // Other patterns
-                Some('*') => {
+                Some((idx, '*')) => {
+                    let idx = offset + idx;
+
+                    tokens.push((current_start, Token::Text(current.clone())));
+                    current.clear();
+
                     match chars.peek() {
                         // Bold
-                        Some('*') => {
+                        Some((_, '*')) => {
                             chars.next(); // take the one we just peeked
-                            tokens.push(Token::Text(current.clone()));
-                            tokens.push(Token::Bold);
-                            current.clear();
+                            tokens.push((idx, Token::Bold));
+                            current_start = idx + 2;
                         }
                         // Italic
                         _ => {
-                            tokens.push(Token::Text(current.clone()));
-                            tokens.push(Token::Italic);
-                            current.clear();
+                            tokens.push((idx, Token::Italic));
+                            current_start = idx + 1;
                         }
                     }
                 }
                 // Default
-                Some(ch) => current.push(ch),
+                Some((_, ch)) => current.push(ch),
+                None if code_active => {
+                    let start = match tokens.last() {
+                        Some((start, Token::Code(_))) => *start,
+                        _ => unreachable!(),
+                    };
+
+                    return Err(ParseError::UnclosedCode {
+                        position: ctx.position(start),
+                        snippet: snippet(&ctx.source[start..]),
+                    });
+                }
                 None => {
-                    tokens.push(Token::Text(current));
+                    tokens.push((current_start, Token::Text(current)));
                     break;
                 }
             }
@@ -238,51 +730,42 @@ impl Parser {
         let mut inlines = vec![];
         let mut stack: Vec<TokenOrInline> = vec![];
 
-        for token in tokens {
+        for (start, token) in tokens {
             match token {
                 Token::Text(txt) => {
-                    let ils = Parser::do_links(txt, linkrefs);
+                    let ils = Parser::do_links(txt, start, ctx)?;
                     if stack.is_empty() {
                         inlines.extend_from_slice(&ils);
                     } else {
-                        let toi = ils
-                            .into_iter()
-                            .map(|il| TokenOrInline::Inline(il))
-                            .collect::<Vec<TokenOrInline>>();
-                        stack.extend_from_slice(&toi);
+                        stack.extend(ils.into_iter().map(TokenOrInline::Inline));
                     }
                 }
                 Token::Code(cd) => {
+                    // Both of the backticks are part of the span
+                    let span = Span::new(start, start + cd.len() + 2);
+
                     if stack.is_empty() {
-                        inlines.push(Inline::Code(cd))
+                        inlines.push(Inline::Code(cd, span))
                     } else {
-                        stack.push(Inline::Code(cd).into())
+                        stack.push(Inline::Code(cd, span).into())
                     }
                 }
-                Token::Italic => {
-                    if stack.contains(&Token::Italic.into()) {
-                        let tokens = Parser::pop_until(&mut stack, Token::Italic.into());
-                        stack.push(
-                            Inline::Italic {
-                                content: TokenOrInline::vec_inlines(tokens),
-                            }
-                            .into(),
-                        )
+                Token::Italic | Token::Bold => {
+                    if stack.iter().any(|toi| toi.is_token(&token)) {
+                        let (opened, contents) = Parser::pop_until(&mut stack, &token);
+                        // A token left in the contents was opened but never
+                        // closed before this one was
+                        let content = TokenOrInline::vec_inlines(contents)
+                            .map_err(|(start, token)| Parser::unclosed(token, start, ctx))?;
+
+                        let span = Span::new(opened, start + token.len());
+                        let inline = match token {
+                            Token::Italic => Inline::Italic { content, span },
+                            _ => Inline::Bold { content, span },
+                        };
+                        stack.push(inline.into())
                     } else {
-                        stack.push(Token::Italic.into())
-                    }
-                }
-                Token::Bold => {
-                    if stack.contains(&Token::Bold.into()) {
-                        let tokens = Parser::pop_until(&mut stack, Token::Bold.into());
-                        stack.push(
-                            Inline::Bold {
-                                content: TokenOrInline::vec_inlines(tokens),
-                            }
-                            .into(),
-                        )
-                    } else {
-                        stack.push(Token::Bold.into())
+                        stack.push(TokenOrInline::Token(start, token))
                     }
                 }
             }
@@ -296,83 +779,146 @@ impl Parser {
             }
         }
 
-        inlines
+        // Anything still on the stack is emphasis that never found its end
+        for toi in stack {
+            if let TokenOrInline::Token(start, token) = toi {
+                return Err(Parser::unclosed(token, start, ctx));
+            }
+        }
+
+        Ok(inlines)
     }
 
-    fn do_links<S: AsRef<str>>(raw: S, linkrefs: &HashMap<String, String>) -> Vec<Inline> {
+    fn unclosed(token: Token, start: usize, ctx: &Context) -> ParseError {
+        let position = ctx.position(start);
+        let snippet = snippet(&ctx.source[start..]);
+
+        match token {
+            Token::Bold => ParseError::UnclosedBold { position, snippet },
+            _ => ParseError::UnclosedItalic { position, snippet },
+        }
+    }
+
+    // offset is where raw starts in the source
+    fn do_links<S: AsRef<str>>(
+        raw: S,
+        offset: usize,
+        ctx: &Context,
+    ) -> Result<Vec<Inline>, ParseError> {
         let raw = raw.as_ref();
 
+        // Empty text is just noise, don't keep it around
+        if raw.is_empty() {
+            return Ok(vec![]);
+        }
+
         // Find absolute links.
         // NOTE: There still may be reference or interlinks before this!
-        match raw.find("{{") {
-            Some(start) => match raw.find("}}") {
-                Some(end) => {
-                    let before = &raw[..start];
-                    let link = &raw[start + 2..end];
-                    let after = &raw[end + 2..];
+        if let Some(start) = find_unescaped(raw, "{{") {
+            if let Some(end) = find_unescaped(&raw[start..], "}}").map(|end| start + end) {
+                let before = &raw[..start];
+                let link = &raw[start + 2..end];
+                let after = &raw[end + 2..];
 
-                    let mut inlines = Self::do_links(before, linkrefs);
-                    inlines.push(Self::absolute_link(link));
-                    inlines.extend_from_slice(&Self::do_links(after, linkrefs));
+                let span = Span::new(offset + start, offset + end + 2);
 
-                    return inlines;
-                }
-                None => (),
-            },
-            None => (),
+                let mut inlines = Self::do_links(before, offset, ctx)?;
+                inlines.push(Self::absolute_link(link, span));
+                inlines.extend_from_slice(&Self::do_links(after, span.end, ctx)?);
+
+                return Ok(inlines);
+            }
         }
 
         // Find reference and interlinks.
-        match raw.find("{") {
-            Some(start) => match raw.find("}") {
-                Some(end) => {
-                    let before = Inline::Text(raw[..start].to_owned());
-                    let link = &raw[start + 1..end];
-                    let after = &raw[end + 1..];
+        if let Some(start) = find_unescaped(raw, "{") {
+            if let Some(end) = find_unescaped(&raw[start..], "}").map(|end| start + end) {
+                let before = &raw[..start];
+                let link = &raw[start + 1..end];
+                let after = &raw[end + 1..];
 
-                    let mut inlines = vec![before, Self::get_link(link, linkrefs)];
-                    inlines.extend_from_slice(&Self::do_links(after, linkrefs));
+                let span = Span::new(offset + start, offset + end + 1);
 
-                    return inlines;
-                }
-                None => (),
-            },
-            None => (),
+                let mut inlines = Self::do_links(before, offset, ctx)?;
+                inlines.push(Self::get_link(link, span, ctx)?);
+                inlines.extend_from_slice(&Self::do_links(after, span.end, ctx)?);
+
+                return Ok(inlines);
+            }
         }
 
-        vec![Inline::Text(raw.to_owned())]
+        // The span is the source as written, escapes and all
+        Ok(vec![Inline::Text(
+            unescape(raw),
+            Span::new(offset, offset + raw.len()),
+        )])
     }
 
-    fn get_link<S: AsRef<str>>(raw: S, linkrefs: &HashMap<String, String>) -> Inline {
+    // span covers the braces as well as raw
+    fn get_link<S: AsRef<str>>(raw: S, span: Span, ctx: &Context) -> Result<Inline, ParseError> {
         let raw = raw.as_ref();
+        let empty = || ParseError::EmptyLink {
+            position: ctx.position(span.start),
+            snippet: snippet(&ctx.source[span.start..span.end]),
+        };
 
         match raw.chars().next() {
+            // Footnote!
+            Some('^') => {
+                let name = raw[1..].trim();
+
+                if name.is_empty() {
+                    return Err(empty());
+                }
+
+                if ctx.defs.footnotes.contains_key(name) {
+                    Ok(Inline::FootnoteReference {
+                        name: name.to_owned(),
+                        span,
+                    })
+                } else {
+                    Err(ParseError::MissingFootnote {
+                        name: name.to_owned(),
+                        position: ctx.position(span.start),
+                        snippet: snippet(&ctx.source[span.start..span.end]),
+                    })
+                }
+            }
             // Reference link!
             Some('!') => {
                 let (name, reference) = Self::parse_link(&raw[1..]);
 
+                if reference.is_empty() {
+                    return Err(empty());
+                }
+
                 let name = if let Some(name) = name {
                     name
                 } else {
                     reference.clone()
                 };
 
-                println!("{{!{} | {}}}", name, reference);
-
-                match linkrefs.get(&reference) {
-                    Some(location) => Inline::ReferenceLink {
+                match ctx.defs.linkrefs.get(&reference) {
+                    Some(location) => Ok(Inline::ReferenceLink {
                         name,
+                        reference,
                         location: location.clone(),
-                    },
-                    None => {
-                        // Recover when we can't find the location by reconstructing the text
-                        Inline::Text(format!("{{{}}}", raw))
-                    }
+                        span,
+                    }),
+                    None => Err(ParseError::MissingReference {
+                        name: reference,
+                        position: ctx.position(span.start),
+                        snippet: snippet(&ctx.source[span.start..span.end]),
+                    }),
                 }
             }
             // Interlink!
             Some(_) => {
-                let (name, location) = Self::parse_link(&raw);
+                let (name, location) = Self::parse_link(raw);
+
+                if location.is_empty() {
+                    return Err(empty());
+                }
 
                 let name = if let Some(name) = name {
                     name
@@ -380,12 +926,14 @@ impl Parser {
                     location.clone()
                 };
 
-                Inline::InterLink { name, location }
-            }
-            None => {
-                // We're a link, but we're an empty link. Give back an Inline text with only {}
-                Inline::Text("{}".to_owned())
+                Ok(Inline::InterLink {
+                    name,
+                    location,
+                    span,
+                })
             }
+            // We're a link, but we're an empty link.
+            None => Err(empty()),
         }
     }
 
@@ -396,50 +944,289 @@ impl Parser {
         }
     }
 
-    fn absolute_link<S: AsRef<str>>(raw: S) -> Inline {
+    fn absolute_link<S: AsRef<str>>(raw: S, span: Span) -> Inline {
         match raw.as_ref().split_once('|') {
             Some((name, link)) => Inline::AbsoluteLink {
                 name: Some(name.trim().to_owned()),
                 location: link.trim().to_owned(),
+                span,
             },
             None => Inline::AbsoluteLink {
                 name: None,
                 location: raw.as_ref().trim().to_owned(),
+                span,
             },
         }
     }
 
-    fn pop_until<T: PartialEq>(haystack: &mut Vec<T>, needle: T) -> Vec<T> {
+    // Returns the offset of the token we stopped at along with everything that
+    // came after it
+    fn pop_until(haystack: &mut Vec<TokenOrInline>, needle: &Token) -> (usize, Vec<TokenOrInline>) {
         let mut ret = vec![];
+        let mut opened = 0;
         loop {
             match haystack.pop() {
-                Some(thing) if thing == needle => break,
+                Some(TokenOrInline::Token(start, token)) if &token == needle => {
+                    opened = start;
+                    break;
+                }
                 Some(thing) => ret.push(thing),
                 None => break,
             }
         }
 
         ret.reverse();
-        ret
+        (opened, ret)
     }
 
+    /// Every top level run of inlines, but not the inlines inside them. Use
+    /// [Parser::visit_mut] to get at all of them.
     pub fn inlines_mut<'a>(&'a mut self) -> InlineIter<'a> {
         InlineIter::new(self)
     }
+
+    pub fn visit<V: Visitor>(&self, visitor: &mut V) {
+        visit::walk_parser(visitor, self)
+    }
+
+    pub fn visit_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+        visit::walk_parser_mut(visitor, self)
+    }
+}
+
+// Gives back every line along with the byte offset it starts at. Lines do not
+// include their ending, just like str::lines
+fn lines_with_offsets(raw: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut offset = 0;
+
+    raw.split_inclusive('\n').map(move |line| {
+        let start = offset;
+        offset += line.len();
+
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        (start, line.strip_suffix('\r').unwrap_or(line))
+    })
+}
+
+// Characters a backslash can escape. Some only mean something at the start of
+// a line, but a line starting with a backslash is never anything but a
+// paragraph, so `\#` and the like come out here too. Ordered lists are escaped
+// after the number, like `1\. not a list`.
+const ESCAPABLE: &[char] = &[
+    '\\', '*', '{', '}', '`', '^', '#', '[', '>', '-', '|', '.', ')',
+];
+
+// Like str::find, but skips over anything that's been escaped
+fn find_unescaped(raw: &str, pat: &str) -> Option<usize> {
+    let mut chars = raw.char_indices();
+
+    while let Some((idx, ch)) = chars.next() {
+        if ch == '\\' {
+            chars.next();
+        } else if raw[idx..].starts_with(pat) {
+            return Some(idx);
+        }
+    }
+
+    None
+}
+
+// Takes the backslash off of escapes, leaving what they escaped
+fn unescape(raw: &str) -> String {
+    let mut ret = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next_if(|ch| ESCAPABLE.contains(ch)) {
+                Some(escaped) => ret.push(escaped),
+                None => ret.push('\\'),
+            },
+            ch => ret.push(ch),
+        }
+    }
+
+    ret
+}
+
+// Splits a table row into its cells. Each one is trimmed and paired with the
+// byte offset it starts at in the line. Pipes in links and code don't count.
+fn split_cells(line: &str) -> Vec<(usize, &str)> {
+    let line = line.trim_end();
+    let inner = line.strip_prefix('|').unwrap_or(line);
+    // Offsets are from the start of inner to begin with, which is past the `|`
+    let inner_offset = line.len() - inner.len();
+    let inner = inner.strip_suffix('|').unwrap_or(inner);
+
+    let mut cells = vec![];
+    let mut cell_start = 0;
+    let mut braces = 0;
+    let mut code_active = false;
+    let mut escaped = false;
+    for (idx, ch) in inner.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+
+        match ch {
+            '\\' if !code_active => escaped = true,
+            '`' => code_active = !code_active,
+            '{' if !code_active => braces += 1,
+            '}' if !code_active && braces > 0 => braces -= 1,
+            '|' if !code_active && braces == 0 => {
+                cells.push((cell_start, &inner[cell_start..idx]));
+                cell_start = idx + 1;
+            }
+            _ => (),
+        }
+    }
+    cells.push((cell_start, &inner[cell_start..]));
+
+    cells
+        .into_iter()
+        .map(|(offset, cell)| {
+            let trimmed = cell.trim_start();
+            let offset = inner_offset + offset + (cell.len() - trimmed.len());
+            (offset, trimmed.trim_end())
+        })
+        .collect()
+}
+
+// The rest of a quoted line, and where it starts, if the line is quoted
+fn quote_line(start: usize, line: &str) -> Option<(usize, &str)> {
+    let rest = line.strip_prefix('>')?;
+
+    match rest.strip_prefix(' ') {
+        Some(rest) => Some((start + 2, rest)),
+        None => Some((start + 1, rest)),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Marker {
+    Unordered,
+    Ordered(usize),
+}
+
+impl Marker {
+    // Start a list of our kind with its first item
+    fn list(self, item: ListItem, span: Span) -> Block {
+        match self {
+            Marker::Unordered => Block::UnorderedList {
+                items: vec![item],
+                span,
+            },
+            Marker::Ordered(start) => Block::OrderedList {
+                start,
+                items: vec![item],
+                span,
+            },
+        }
+    }
+}
+
+// A line that starts a list item, like `- text` or `  2. text`
+struct ListLine<'a> {
+    // Tabs count as four
+    indent: usize,
+    marker: Marker,
+    text: &'a str,
+    // Where the text starts in the line, in bytes
+    text_offset: usize,
+}
+
+impl<'a> ListLine<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let trimmed = line.trim_start();
+        let whitespace = &line[..line.len() - trimmed.len()];
+        let indent = whitespace
+            .chars()
+            .map(|ch| if ch == '\t' { 4 } else { 1 })
+            .sum();
+
+        let (marker, text) = match trimmed.strip_prefix("- ") {
+            Some(text) => (Marker::Unordered, text),
+            None => {
+                let (number, text) = trimmed.split_once(". ")?;
+
+                if number.is_empty() || !number.chars().all(|ch| ch.is_ascii_digit()) {
+                    return None;
+                }
+
+                (Marker::Ordered(number.parse().ok()?), text)
+            }
+        };
+
+        Some(Self {
+            indent,
+            marker,
+            text,
+            text_offset: line.len() - text.len(),
+        })
+    }
+}
+
+// Things defined on a line of their own, like `[Name]: url`, that the rest of
+// the page can refer to
+#[derive(Default)]
+struct Definitions {
+    linkrefs: HashMap<String, String>,
+    // Footnote text stays a single Text until the second pass
+    footnotes: HashMap<String, Vec<Inline>>,
+}
+
+// Everything the inline parsing needs to know about the document as a whole
+struct Context<'a> {
+    source: &'a str,
+    defs: Definitions,
+}
+
+impl<'a> Context<'a> {
+    fn position(&self, offset: usize) -> Position {
+        Position::from_offset(self.source, offset)
+    }
 }
 
 pub struct InlineIter<'a> {
-    blocks: Option<std::slice::IterMut<'a, Block>>,
-    inlines: Option<std::slice::IterMut<'a, Inline>>,
+    // Every run of inlines we have yet to go through, last first
+    inlines: Vec<std::slice::IterMut<'a, Inline>>,
 }
 
 impl<'a> InlineIter<'a> {
     fn new(parser: &'a mut Parser) -> Self {
-        let blocks = parser.blocks.iter_mut();
+        let mut inlines = vec![];
+        Self::collect(&mut parser.blocks, &mut inlines);
+        for content in parser.footnotes.values_mut() {
+            inlines.push(content.iter_mut());
+        }
+        inlines.reverse();
+
+        Self { inlines }
+    }
 
-        Self {
-            blocks: Some(blocks),
-            inlines: None,
+    fn collect(blocks: &'a mut [Block], inlines: &mut Vec<std::slice::IterMut<'a, Inline>>) {
+        for block in blocks {
+            match block {
+                Block::Header { content, .. } => inlines.push(content.iter_mut()),
+                Block::Paragraph { content, .. } => inlines.push(content.iter_mut()),
+                Block::UnorderedList { items, .. } | Block::OrderedList { items, .. } => {
+                    for item in items {
+                        inlines.push(item.content.iter_mut());
+                        Self::collect(&mut item.children, inlines);
+                    }
+                }
+                Block::Quote { content, .. } => Self::collect(content, inlines),
+                Block::Table { header, rows, .. } => {
+                    for row in header.iter_mut().chain(rows.iter_mut()) {
+                        for cell in row.cells.iter_mut() {
+                            inlines.push(cell.iter_mut());
+                        }
+                    }
+                }
+                Block::CodeBlock { .. } => continue,
+                Block::Image { .. } => continue,
+            }
         }
     }
 }
@@ -448,38 +1235,13 @@ impl<'a> Iterator for InlineIter<'a> {
     type Item = &'a mut Inline;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(ref mut inline_iter) = self.inlines {
-            match inline_iter.next() {
+        loop {
+            match self.inlines.last_mut()?.next() {
                 Some(inline) => return Some(inline),
-                None => (),
-            }
-        }
-
-        if let Some(ref mut block_iter) = self.blocks {
-            loop {
-                match block_iter.next() {
-                    None => {
-                        self.blocks = None;
-                        return None;
-                    }
-                    Some(block) => match block {
-                        Block::Header { content, .. } => {
-                            self.inlines = Some(content.iter_mut());
-                            break;
-                        }
-                        Block::Paragraph { content } => {
-                            self.inlines = Some(content.iter_mut());
-                            break;
-                        }
-                        Block::CodeBlock { .. } => continue,
-                        Block::Image { .. } => continue,
-                    },
+                None => {
+                    self.inlines.pop();
                 }
             }
-
-            self.next()
-        } else {
-            None
         }
     }
 }
@@ -488,51 +1250,150 @@ impl FromStr for Parser {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (blocks, linkrefs) = Self::first_pass(s)?;
-        let blocks = Self::second_pass(blocks, linkrefs)?;
+        let (mut blocks, defs) = Self::first_pass(s)?;
+        let metadata = Self::take_metadata(&mut blocks);
+
+        let ctx = Context { source: s, defs };
+        let blocks = Self::second_pass(blocks, &ctx)?;
+
+        // In the order they're written so the first error is the one reported
+        let mut definitions: Vec<_> = ctx.defs.footnotes.iter().collect();
+        definitions.sort_by_key(|(_, content)| content.first().map(|il| il.span().start));
+
+        let mut footnotes = HashMap::new();
+        for (name, content) in definitions {
+            footnotes.insert(name.clone(), Self::parse_inlines(content, &ctx)?);
+        }
 
-        Ok(Self { blocks })
+        Ok(Self {
+            blocks,
+            metadata,
+            footnotes,
+            references: ctx.defs.linkrefs,
+        })
     }
 }
 
 #[derive(Debug, Error)]
-pub enum ParseError {}
+pub enum ParseError {
+    #[error("{position}: code block is never closed: {snippet}")]
+    UnclosedCodeBlock { position: Position, snippet: String },
+    #[error("{position}: code is never closed: {snippet}")]
+    UnclosedCode { position: Position, snippet: String },
+    #[error("{position}: italic is never closed: {snippet}")]
+    UnclosedItalic { position: Position, snippet: String },
+    #[error("{position}: bold is never closed: {snippet}")]
+    UnclosedBold { position: Position, snippet: String },
+    #[error("{position}: alt text does not follow a link: {snippet}")]
+    DanglingAlt { position: Position, snippet: String },
+    #[error("{position}: link is empty: {snippet}")]
+    EmptyLink { position: Position, snippet: String },
+    #[error("{position}: no reference named {name}: {snippet}")]
+    MissingReference {
+        name: String,
+        position: Position,
+        snippet: String,
+    },
+    #[error("{position}: no footnote named {name}: {snippet}")]
+    MissingFootnote {
+        name: String,
+        position: Position,
+        snippet: String,
+    },
+}
+
+impl ParseError {
+    pub fn position(&self) -> Position {
+        match self {
+            ParseError::UnclosedCodeBlock { position, .. }
+            | ParseError::UnclosedCode { position, .. }
+            | ParseError::UnclosedItalic { position, .. }
+            | ParseError::UnclosedBold { position, .. }
+            | ParseError::DanglingAlt { position, .. }
+            | ParseError::EmptyLink { position, .. }
+            | ParseError::MissingReference { position, .. }
+            | ParseError::MissingFootnote { position, .. } => *position,
+        }
+    }
+}
+
+/// A place in the source text. Both the line and column start at 1, and the
+/// column counts characters, not bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+
+    /// Find the line and column of a byte offset into the source. The offset
+    /// is usually the start, or end, of a [Span].
+    pub fn from_offset<S: AsRef<str>>(source: S, offset: usize) -> Self {
+        let before = &source.as_ref()[..offset];
+
+        match before.rfind('\n') {
+            Some(newline) => Self::new(
+                before.matches('\n').count() + 1,
+                before[newline + 1..].chars().count() + 1,
+            ),
+            None => Self::new(1, before.chars().count() + 1),
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+// Just enough of the source to be recognisable in an error. Never goes past
+// the end of the line.
+fn snippet<S: AsRef<str>>(raw: S) -> String {
+    const MAX_CHARS: usize = 32;
+
+    let raw = raw.as_ref();
+    let raw = raw.lines().next().unwrap_or_default();
+    match raw.char_indices().nth(MAX_CHARS) {
+        Some((idx, _)) => format!("{}...", &raw[..idx]),
+        None => raw.to_owned(),
+    }
+}
 
 #[derive(Clone, PartialEq)]
 enum TokenOrInline {
-    Token(Token),
+    // Tokens remember their byte offset so we can say where they were opened
+    Token(usize, Token),
     Inline(Inline),
 }
 
 impl TokenOrInline {
-    fn vec_inlines(vec: Vec<Self>) -> Vec<Inline> {
+    // Gives back the first token, and its offset, if there is one in the vec
+    fn vec_inlines(vec: Vec<Self>) -> Result<Vec<Inline>, (usize, Token)> {
         let mut ret = vec![];
 
         for v in vec {
-            if let TokenOrInline::Inline(inline) = v {
-                ret.push(inline);
-            } else {
-                panic!("Token in vec_inlines")
+            match v {
+                TokenOrInline::Inline(inline) => ret.push(inline),
+                TokenOrInline::Token(offset, token) => return Err((offset, token)),
             }
         }
 
-        ret
+        Ok(ret)
     }
 
-    fn is_token(&self) -> bool {
+    fn is_token(&self, token: &Token) -> bool {
         match self {
-            TokenOrInline::Token(_) => true,
+            TokenOrInline::Token(_, tok) => tok == token,
             _ => false,
         }
     }
 }
 
-impl From<Token> for TokenOrInline {
-    fn from(t: Token) -> Self {
-        TokenOrInline::Token(t)
-    }
-}
-
 impl From<Inline> for TokenOrInline {
     fn from(t: Inline) -> Self {
         TokenOrInline::Inline(t)
@@ -548,13 +1409,9 @@ enum Token {
 }
 
 impl Token {
-    fn vec_string(tokens: Vec<Token>) -> String {
-        let mut ret = String::new();
-        for token in tokens {
-            ret.push_str(&token.to_string());
-        }
-
-        ret
+    // How many bytes the token took up in the source
+    fn len(&self) -> usize {
+        self.to_string().len()
     }
 }
 
@@ -571,8 +1428,427 @@ impl fmt::Display for Token {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn meta_parses() {
+        let meta = include_str!("../../test_source/meta.md");
+        assert!(meta.parse::<Parser>().is_ok());
+    }
+
+    #[test]
+    fn unclosed_code_block() {
+        let err = "Words\n\n```rust\nfn main() {}\n"
+            .parse::<Parser>()
+            .err()
+            .unwrap();
+
+        match err {
+            ParseError::UnclosedCodeBlock { position, snippet } => {
+                assert_eq!(position, Position::new(3, 1));
+                assert_eq!(snippet, "```rust");
+            }
+            _ => panic!("wrong error: {}", err),
+        }
+    }
+
+    #[test]
+    fn unclosed_code() {
+        let err = "Fine line\nsome `code".parse::<Parser>().err().unwrap();
+        assert!(matches!(err, ParseError::UnclosedCode { .. }));
+        assert_eq!(err.position(), Position::new(2, 6));
+
+        // An escaped backtick doesn't open anything
+        assert!("some \\`code".parse::<Parser>().is_ok());
+    }
+
+    #[test]
+    fn unclosed_emphasis() {
+        let err = "Fine line\nsome *italic".parse::<Parser>().err().unwrap();
+        assert!(matches!(err, ParseError::UnclosedItalic { .. }));
+        assert_eq!(err.position(), Position::new(2, 6));
+
+        let err = "## a **bold".parse::<Parser>().err().unwrap();
+        assert!(matches!(err, ParseError::UnclosedBold { .. }));
+        assert_eq!(err.position(), Position::new(1, 6));
+    }
+
+    #[test]
+    fn misnested_emphasis() {
+        let err = "*a **b* c**".parse::<Parser>().err().unwrap();
+        assert!(matches!(err, ParseError::UnclosedBold { .. }));
+        assert_eq!(err.position(), Position::new(1, 4));
+    }
+
+    #[test]
+    fn dangling_alt() {
+        let err = "^ no link here".parse::<Parser>().err().unwrap();
+        assert!(matches!(err, ParseError::DanglingAlt { .. }));
+
+        let err = "not a link\n^ alt".parse::<Parser>().err().unwrap();
+        assert_eq!(err.position(), Position::new(2, 1));
+    }
+
+    #[test]
+    fn empty_link() {
+        let err = "a {} b".parse::<Parser>().err().unwrap();
+
+        match err {
+            ParseError::EmptyLink { position, snippet } => {
+                assert_eq!(position, Position::new(1, 3));
+                assert_eq!(snippet, "{}");
+            }
+            _ => panic!("wrong error: {}", err),
+        }
+    }
+
+    #[test]
+    fn missing_reference() {
+        let err = "{{https://nyble.dev}} and {!Nowhere}"
+            .parse::<Parser>()
+            .err()
+            .unwrap();
+
+        match err {
+            ParseError::MissingReference { name, position, .. } => {
+                assert_eq!(name, "Nowhere");
+                assert_eq!(position, Position::new(1, 27));
+            }
+            _ => panic!("wrong error: {}", err),
+        }
+    }
+
+    #[test]
+    fn image_from_reference() {
+        let parser: Parser = "{!Img}\n^ alt\n\n[Img]: https://example.com/a.png"
+            .parse()
+            .unwrap();
+
+        match &parser.blocks[..] {
+            [Block::Image { link, alt, span }] => {
+                assert_eq!(link.location(), Some("https://example.com/a.png"));
+                assert_eq!(alt, "alt");
+                assert_eq!(*span, Span::new(0, 12));
+            }
+            blocks => panic!("expected a lone image, got {:?}", blocks),
+        }
+    }
+
+    #[test]
+    fn block_spans() {
+        let source = "# Title\n\nfirst line\nsecond\n\n```\ncode\n```\n";
+        let parser: Parser = source.parse().unwrap();
+
+        let spans: Vec<&str> = parser
+            .blocks
+            .iter()
+            .map(|block| &source[block.span().start..block.span().end])
+            .collect();
+
+        assert_eq!(
+            spans,
+            vec!["# Title", "first line\nsecond", "```\ncode\n```"]
+        );
+    }
+
+    #[test]
+    fn inline_spans() {
+        let source = "a *b {c}* `d`";
+        let parser: Parser = source.parse().unwrap();
+
+        let content = match &parser.blocks[0] {
+            Block::Paragraph { content, .. } => content,
+            _ => unreachable!(),
+        };
+
+        let spans: Vec<&str> = content
+            .iter()
+            .map(|inline| &source[inline.span().start..inline.span().end])
+            .collect();
+        assert_eq!(spans, vec!["a ", "*b {c}*", " ", "`d`"]);
+
+        match &content[1] {
+            Inline::Italic { content, .. } => {
+                assert_eq!(content[1].span(), Span::new(5, 8));
+            }
+            _ => panic!("expected italic"),
+        }
+    }
+
+    #[test]
+    fn position_from_offset() {
+        let source = "one\ntwo\nthree";
+
+        assert_eq!(Position::from_offset(source, 0), Position::new(1, 1));
+        assert_eq!(Position::from_offset(source, 4), Position::new(2, 1));
+        assert_eq!(Position::from_offset(source, 10), Position::new(3, 3));
+    }
+
+    #[test]
+    fn nested_lists() {
+        let source = "Notes:\n- one\n  1. inner\n  2. *second*\n     carried on\n- two\n\nafter";
+        let parser: Parser = source.parse().unwrap();
+
+        assert_eq!(parser.blocks.len(), 3);
+        let items = match &parser.blocks[1] {
+            Block::UnorderedList { items, span } => {
+                assert_eq!(&source[span.start..span.end], &source[7..source.len() - 7]);
+                items
+            }
+            block => panic!("expected an unordered list, got {:?}", block),
+        };
+
+        assert_eq!(items.len(), 2);
+        match &items[0].children[..] {
+            [Block::OrderedList { start, items, .. }] => {
+                assert_eq!(*start, 1);
+                assert_eq!(items.len(), 2);
+                assert!(matches!(items[1].content[0], Inline::Italic { .. }));
+                assert!(matches!(items[1].content[1], Inline::SoftBreak(_)));
+            }
+            children => panic!("expected a nested ordered list, got {:?}", children),
+        }
+        assert!(items[1].children.is_empty());
+    }
+
+    #[test]
+    fn stepping_back_partway() {
+        let parser: Parser = "- a\n    - b\n  - c\n- d".parse().unwrap();
+
+        let items = match &parser.blocks[..] {
+            [Block::UnorderedList { items, .. }] => items,
+            blocks => panic!("expected one list, got {:?}", blocks),
+        };
+
+        assert_eq!(items.len(), 2);
+        match &items[0].children[..] {
+            [Block::UnorderedList { items, .. }] => assert_eq!(items.len(), 2),
+            children => panic!("expected one nested list, got {:?}", children),
+        }
+    }
+
+    #[test]
+    fn list_kind_switch() {
+        let parser: Parser = "- a\n- b\n3. c".parse().unwrap();
+
+        match &parser.blocks[..] {
+            [Block::UnorderedList { .. }, Block::OrderedList { start, .. }] => {
+                assert_eq!(*start, 3)
+            }
+            blocks => panic!("expected two lists, got {:?}", blocks),
+        }
+    }
+
+    #[test]
+    fn quote_with_blocks() {
+        let source = "> Said *well*\n> more\n>\n> ```\n> code\n> ```\nafter";
+        let parser: Parser = source.parse().unwrap();
+
+        let content = match &parser.blocks[..] {
+            [Block::Quote { content, span }, Block::Paragraph { .. }] => {
+                assert_eq!(&source[span.start..span.end], &source[..source.len() - 6]);
+                content
+            }
+            blocks => panic!("expected a quote and a paragraph, got {:?}", blocks),
+        };
+
+        match &content[..] {
+            [Block::Paragraph { content, .. }, Block::CodeBlock { content: code, .. }] => {
+                assert_eq!(code, "code\n");
+                assert_eq!(
+                    &source[content[1].span().start..content[1].span().end],
+                    "*well*"
+                );
+            }
+            blocks => panic!("expected a paragraph and code, got {:?}", blocks),
+        }
+    }
+
+    #[test]
+    fn error_inside_quote() {
+        let err = "> fine\n> not **fine".parse::<Parser>().err().unwrap();
+        assert_eq!(err.position(), Position::new(2, 7));
+    }
+
+    #[test]
+    fn table_with_header() {
+        let source = "| Name | {Link | there} |\n|:--|--:|\n| *a* | `b|c` |\n| d |";
+        let parser: Parser = source.parse().unwrap();
+
+        let (header, alignments, rows) = match &parser.blocks[..] {
+            [Block::Table {
+                header: Some(header),
+                alignments,
+                rows,
+                ..
+            }] => (header, alignments, rows),
+            blocks => panic!("expected a table with a header, got {:?}", blocks),
+        };
+
+        assert_eq!(alignments, &vec![Alignment::Left, Alignment::Right]);
+        assert_eq!(header.cells.len(), 2);
+        assert!(matches!(header.cells[1][..], [Inline::InterLink { .. }]));
+
+        assert_eq!(rows.len(), 2);
+        assert!(matches!(rows[0].cells[0][..], [Inline::Italic { .. }]));
+        match &rows[0].cells[1][..] {
+            [Inline::Code(code, span)] => {
+                assert_eq!(code, "b|c");
+                assert_eq!(&source[span.start..span.end], "`b|c`");
+            }
+            cell => panic!("expected code, got {:?}", cell),
+        }
+        assert_eq!(rows[1].cells.len(), 1);
+    }
+
+    #[test]
+    fn table_without_header() {
+        let parser: Parser = "| a | b |\n| c | d |".parse().unwrap();
+
+        match &parser.blocks[..] {
+            [Block::Table {
+                header: None,
+                alignments,
+                rows,
+                ..
+            }] => {
+                assert!(alignments.is_empty());
+                assert_eq!(rows.len(), 2);
+            }
+            blocks => panic!("expected a table without a header, got {:?}", blocks),
+        }
+    }
+
+    #[test]
+    fn metadata_block() {
+        let meta = include_str!("../../test_source/meta.md");
+        let parser: Parser = meta.parse().unwrap();
+
+        assert_eq!(parser.meta("Title"), Some("Meta"));
+        assert!(matches!(parser.blocks[0], Block::Header { level: 1, .. }));
+    }
+
+    #[test]
+    fn metadata_keeps_order_and_children() {
+        let parser: Parser = "```metadata\nTitle T\nAuthor\n    Name Me\n    Site x\nDate now\n```"
+            .parse()
+            .unwrap();
+
+        let keys: Vec<&str> = parser.metadata.iter().map(|m| m.key.as_str()).collect();
+        assert_eq!(keys, vec!["Title", "Author", "Date"]);
+        assert_eq!(parser.metadata[1].children.len(), 2);
+        assert_eq!(parser.metadata[1].children[0].value, "Me");
+        assert_eq!(parser.meta("Name"), None);
+    }
+
+    #[test]
+    fn metadata_only_at_start() {
+        let parser: Parser = "Words\n\n```metadata\nTitle Late\n```".parse().unwrap();
+
+        assert!(parser.metadata.is_empty());
+        assert_eq!(parser.blocks.len(), 2);
+    }
+
+    #[test]
+    fn footnotes() {
+        let source = "Cited{^one}, *twice{^one}*\n\n[^one]: See {{https://nyble.dev}}";
+        let parser: Parser = source.parse().unwrap();
+
+        match &parser.blocks[0] {
+            Block::Paragraph { content, .. } => {
+                assert!(
+                    matches!(&content[1], Inline::FootnoteReference { name, .. } if name == "one")
+                )
+            }
+            block => panic!("expected a paragraph, got {:?}", block),
+        }
+
+        match &parser.footnotes["one"][..] {
+            [Inline::Text(txt, _), Inline::AbsoluteLink { span, .. }] => {
+                assert_eq!(txt, "See ");
+                assert_eq!(&source[span.start..span.end], "{{https://nyble.dev}}");
+            }
+            content => panic!("unexpected footnote content {:?}", content),
+        }
+    }
+
+    #[test]
+    fn missing_footnote() {
+        let err = "Cited{^nope}".parse::<Parser>().err().unwrap();
+
+        match err {
+            ParseError::MissingFootnote { name, position, .. } => {
+                assert_eq!(name, "nope");
+                assert_eq!(position, Position::new(1, 6));
+            }
+            _ => panic!("wrong error: {}", err),
+        }
+    }
+
+    #[test]
+    fn first_broken_footnote() {
+        let source = "[^e]: *one\n[^d]: *two\n[^c]: *three\n[^b]: *four\n[^a]: *five";
+        let err = source.parse::<Parser>().err().unwrap();
+        assert_eq!(err.position(), Position::new(1, 7));
+    }
+
+    #[test]
+    fn inline_escapes() {
+        let source = r"\*not italic\* \{not a link\} \`not code\` \\*italic*";
+        let parser: Parser = source.parse().unwrap();
+
+        match &parser.blocks[0] {
+            Block::Paragraph { content, .. } => match &content[..] {
+                [Inline::Text(txt, span), Inline::Italic { .. }] => {
+                    assert_eq!(txt, r"*not italic* {not a link} `not code` \");
+                    assert_eq!(&source[span.start..span.end], &source[..source.len() - 8]);
+                }
+                content => panic!("unexpected content {:?}", content),
+            },
+            block => panic!("expected a paragraph, got {:?}", block),
+        }
+    }
+
+    #[test]
+    fn line_start_escapes() {
+        let parser: Parser = "\\# not a header\n\\[x]: not a reference\n\\^ or alt"
+            .parse()
+            .unwrap();
+
+        match &parser.blocks[..] {
+            [Block::Paragraph { content, .. }] => {
+                let texts: Vec<&str> = content
+                    .iter()
+                    .filter_map(|inline| match inline {
+                        Inline::Text(txt, _) => Some(txt.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+
+                assert_eq!(
+                    texts,
+                    vec!["# not a header", "[x]: not a reference", "^ or alt"]
+                );
+            }
+            blocks => panic!("expected one paragraph, got {:?}", blocks),
+        }
+    }
+
+    #[test]
+    fn escaped_table_pipe() {
+        let parser: Parser = r"| a \| b | c |".parse().unwrap();
+
+        match &parser.blocks[0] {
+            Block::Table { rows, .. } => {
+                assert_eq!(rows[0].cells.len(), 2);
+                assert!(matches!(&rows[0].cells[0][..], [Inline::Text(txt, _)] if txt == "a | b"));
+            }
+            block => panic!("expected a table, got {:?}", block),
+        }
+    }
 }
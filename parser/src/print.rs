@@ -0,0 +1,405 @@
+//! Printing a [Parser] back out as source markup.
+//!
+//! The output is canonical rather than what was originally written: blocks are
+//! separated by one empty line, lists are indented by the width of their
+//! marker, and reference and footnote definitions go at the end of the page in
+//! the order they're first used. Parsing the output gives back the same tree.
+
+use std::fmt;
+
+use crate::{
+    element::{Alignment, Block, Inline, ListItem, Span},
+    visit::{self, Visitor},
+    Meta, Parser, ESCAPABLE,
+};
+
+impl fmt::Display for Parser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sections = vec![];
+
+        if !self.metadata.is_empty() {
+            let mut meta = String::from("```metadata\n");
+            metadata(&self.metadata, 0, &mut meta);
+            meta.push_str("```");
+            sections.push(meta);
+        }
+
+        if !self.blocks.is_empty() {
+            sections.push(blocks(&self.blocks));
+        }
+
+        let mut used = Used::default();
+        visit::walk_parser(&mut used, self);
+
+        let references: Vec<String> = in_use_order(self.references.keys(), &used.references)
+            .into_iter()
+            .map(|name| format!("[{}]: {}", name, self.references[name]))
+            .collect();
+        if !references.is_empty() {
+            sections.push(references.join("\n"));
+        }
+
+        let footnotes: Vec<String> = in_use_order(self.footnotes.keys(), &used.footnotes)
+            .into_iter()
+            .map(|name| format!("[^{}]: {}", name, inlines(&self.footnotes[name], &[])))
+            .collect();
+        if !footnotes.is_empty() {
+            sections.push(footnotes.join("\n"));
+        }
+
+        match sections.is_empty() {
+            true => Ok(()),
+            false => writeln!(f, "{}", sections.join("\n\n")),
+        }
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", block(self))
+    }
+}
+
+// Every name that's in use, in the order it was first used, then the rest of
+// them sorted so the output doesn't depend on the HashMap
+fn in_use_order<'a, I>(names: I, used: &[String]) -> Vec<&'a str>
+where
+    I: Iterator<Item = &'a String>,
+{
+    let (mut used_names, mut unused): (Vec<&str>, Vec<&str>) = names
+        .map(String::as_str)
+        .partition(|name| used.iter().any(|u| u == name));
+
+    used_names.sort_by_key(|name| used.iter().position(|u| u == name));
+    unused.sort_unstable();
+
+    used_names.into_iter().chain(unused).collect()
+}
+
+// The references and footnotes the page uses, in the order it uses them
+#[derive(Default)]
+struct Used {
+    references: Vec<String>,
+    footnotes: Vec<String>,
+}
+
+impl Visitor for Used {
+    fn visit_reference_link(&mut self, _name: &str, reference: &str, _location: &str, _: Span) {
+        if !self.references.iter().any(|r| r == reference) {
+            self.references.push(reference.to_owned());
+        }
+    }
+
+    fn visit_footnote_reference(&mut self, name: &str, _span: Span) {
+        if !self.footnotes.iter().any(|n| n == name) {
+            self.footnotes.push(name.to_owned());
+        }
+    }
+}
+
+// In the order they were written, children indented four spaces a level
+fn metadata(metas: &[Meta], depth: usize, out: &mut String) {
+    for meta in metas {
+        out.push_str(&"    ".repeat(depth));
+        match meta.value.as_str() {
+            "" => out.push_str(&meta.key),
+            value => out.push_str(&format!("{} {}", meta.key, value)),
+        }
+        out.push('\n');
+
+        metadata(&meta.children, depth + 1, out);
+    }
+}
+
+fn blocks(blocks: &[Block]) -> String {
+    blocks.iter().map(block).collect::<Vec<_>>().join("\n\n")
+}
+
+fn block(block: &Block) -> String {
+    match block {
+        Block::Header { level, content, .. } => {
+            format!("{} {}", "#".repeat(*level as usize), inlines(content, &[]))
+        }
+        Block::Paragraph { content, .. } => lines(content, ""),
+        Block::CodeBlock {
+            language, content, ..
+        } => format!("```{}\n{}```", language, content),
+        Block::Image { link, alt, .. } => match alt.as_str() {
+            "" => format!("{}\n^", inline(link, &[], false)),
+            alt => format!("{}\n^ {}", inline(link, &[], false), alt),
+        },
+        // Without the `>` there'd be nothing to parse the quote back from
+        Block::Quote { content, .. } if content.is_empty() => String::from(">"),
+        Block::Quote { content, .. } => blocks(content)
+            .lines()
+            .map(|line| match line {
+                "" => String::from(">"),
+                line => format!("> {}", line),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::Table {
+            header,
+            alignments,
+            rows,
+            ..
+        } => {
+            let mut lines = vec![];
+
+            if let Some(header) = header {
+                lines.push(table_row(&header.cells));
+            }
+
+            if !alignments.is_empty() {
+                let separator: Vec<&str> = alignments
+                    .iter()
+                    .map(|alignment| match alignment {
+                        Alignment::None => "---",
+                        Alignment::Left => ":--",
+                        Alignment::Center => ":-:",
+                        Alignment::Right => "--:",
+                    })
+                    .collect();
+                lines.push(format!("| {} |", separator.join(" | ")));
+            }
+
+            for row in rows {
+                lines.push(table_row(&row.cells));
+            }
+
+            lines.join("\n")
+        }
+        Block::UnorderedList { items, .. } => list_items(items, |_| String::from("- ")),
+        Block::OrderedList { start, items, .. } => {
+            list_items(items, |idx| format!("{}. ", start + idx))
+        }
+    }
+}
+
+fn table_row(cells: &[Vec<Inline>]) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .map(|cell| {
+            let cell = inlines(cell, &['|']);
+
+            // A cell that looks like part of the separator would be taken as one
+            match Parser::cell_alignment(&cell) {
+                Some(_) => format!("\\{}", cell),
+                None => cell,
+            }
+        })
+        .collect();
+
+    format!("| {} |", cells.join(" | "))
+}
+
+// Children are indented by the width of the marker so they nest under the item
+fn list_items<F>(items: &[ListItem], marker: F) -> String
+where
+    F: Fn(usize) -> String,
+{
+    let mut ret = vec![];
+
+    for (idx, item) in items.iter().enumerate() {
+        let marker = marker(idx);
+        let indent = " ".repeat(marker.len());
+
+        ret.push(format!("{}{}", marker, lines(&item.content, &indent)));
+
+        for child in &item.children {
+            for line in block(child).lines() {
+                ret.push(format!("{}{}", indent, line));
+            }
+        }
+    }
+
+    ret.join("\n")
+}
+
+// Inlines that might be broken over a few lines. Every line after the first
+// starts with indent. When there is an indent the first line follows a list
+// marker, so it can start with anything.
+fn lines(content: &[Inline], indent: &str) -> String {
+    content
+        .split(|inline| matches!(inline, Inline::SoftBreak(_)))
+        .enumerate()
+        .map(|(idx, line)| {
+            let line = inlines(line, &[]);
+
+            match idx {
+                0 if !indent.is_empty() => line,
+                0 => line_start(line),
+                _ => format!("{}{}", indent, line_start(line)),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Escapes the start of a line that would otherwise be read as something other
+// than the text it is
+fn line_start(line: String) -> String {
+    let header = match line.split_once(' ') {
+        Some((hashes, _)) => {
+            !hashes.is_empty() && hashes.len() <= 4 && hashes.chars().all(|ch| ch == '#')
+        }
+        None => false,
+    };
+    let reference = line.starts_with('[') && line.contains("]: ");
+
+    // The number stays as it is and the dot after it is escaped instead
+    if let Some((number, rest)) = line.split_once(". ") {
+        if !number.is_empty() && number.chars().all(|ch| ch.is_ascii_digit()) {
+            return format!("{}\\. {}", number, rest);
+        }
+    }
+
+    if header || reference || line.starts_with(&['>', '|', '^'][..]) || line.starts_with("- ") {
+        format!("\\{}", line)
+    } else {
+        line
+    }
+}
+
+// extra are characters to escape on top of the ones that always need it
+fn inlines(content: &[Inline], extra: &[char]) -> String {
+    let mut ret = String::new();
+
+    for (idx, il) in content.iter().enumerate() {
+        // Whatever comes next might start with something a backslash escapes
+        let followed = !matches!(content.get(idx + 1), None | Some(Inline::SoftBreak(_)));
+        ret.push_str(&inline(il, extra, followed));
+    }
+
+    ret
+}
+
+fn inline(inline: &Inline, extra: &[char], followed: bool) -> String {
+    match inline {
+        Inline::SoftBreak(_) => String::from("\n"),
+        Inline::Text(txt, _) => text(txt, extra, followed),
+        Inline::Code(code, _) => format!("`{}`", code),
+        Inline::Italic { content, .. } => format!("*{}*", inlines(content, extra)),
+        Inline::Bold { content, .. } => format!("**{}**", inlines(content, extra)),
+        Inline::AbsoluteLink {
+            name: Some(name),
+            location,
+            ..
+        } => format!("{{{{{} | {}}}}}", name, location),
+        Inline::AbsoluteLink { location, .. } => format!("{{{{{}}}}}", location),
+        Inline::ReferenceLink {
+            name, reference, ..
+        } if name == reference => format!("{{!{}}}", reference),
+        Inline::ReferenceLink {
+            name, reference, ..
+        } => format!("{{!{} | {}}}", name, reference),
+        Inline::InterLink { name, location, .. } if name == location => {
+            format!("{{{}}}", location)
+        }
+        Inline::InterLink { name, location, .. } => format!("{{{} | {}}}", name, location),
+        Inline::FootnoteReference { name, .. } => format!("{{^{}}}", name),
+    }
+}
+
+// A backslash only needs escaping when it would escape what comes after it
+fn text(raw: &str, extra: &[char], followed: bool) -> String {
+    let mut ret = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.peek() {
+                Some(next) if ESCAPABLE.contains(next) => ret.push_str("\\\\"),
+                None if followed => ret.push_str("\\\\"),
+                _ => ret.push('\\'),
+            },
+            '*' | '{' | '}' | '`' => {
+                ret.push('\\');
+                ret.push(ch);
+            }
+            ch if extra.contains(&ch) => {
+                ret.push('\\');
+                ret.push(ch);
+            }
+            ch => ret.push(ch),
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Printing what was parsed from printed source shouldn't change anything
+    fn assert_stable(source: &str) -> String {
+        let printed = source.parse::<Parser>().unwrap().to_string();
+        let reprinted = printed.parse::<Parser>().unwrap().to_string();
+        assert_eq!(printed, reprinted);
+
+        printed
+    }
+
+    #[test]
+    fn prints_canonical() {
+        let source = "```metadata\nTitle Things\n```\n#  Title\nSome *text* and {!ref}.\n\
+            {a | b}{^n}\n\n{{https://example.com/x.png}}\n^ An image\n\n\
+            [ref]: https://example.com\n\n[^n]: A **note**\n\n\
+            > quoted\n>\n> ```rust\n> fn main() {}\n> ```\n\n\
+            |a|b|\n|:-|-:|\n|c|{d}|\n\n>\n\n- one\n  more\n- two\n  3. three";
+
+        assert_eq!(
+            assert_stable(source),
+            "```metadata\nTitle Things\n```\n\n#  Title\n\n\
+            Some *text* and {!ref}.\n{a | b}{^n}\n\n\
+            {{https://example.com/x.png}}\n^ An image\n\n\
+            > quoted\n>\n> ```rust\n> fn main() {}\n> ```\n\n\
+            | a | b |\n| :-- | --: |\n| c | {d} |\n\n>\n\n\
+            - one\n  more\n- two\n  3. three\n\n\
+            [ref]: https://example.com\n\n[^n]: A **note**\n"
+        );
+    }
+
+    #[test]
+    fn escapes_what_needs_it() {
+        let source = "\\# not a header \\*or\\* \\{link\\} \\`code\\` C:\\path\n\
+            \\- nor a list\n12\\. nor this\n\n- \\- item\n  3\\. more\n\n\
+            | \\--- | a \\| b |";
+
+        assert_eq!(
+            assert_stable(source),
+            "\\# not a header \\*or\\* \\{link\\} \\`code\\` C:\\path\n\
+            \\- nor a list\n12\\. nor this\n\n- - item\n  3\\. more\n\n\
+            | \\--- | a \\| b |\n"
+        );
+
+        let parser: Parser = "12\\. nor this".parse().unwrap();
+        assert!(matches!(parser.blocks[..], [Block::Paragraph { .. }]));
+    }
+
+    #[test]
+    fn metadata_as_written() {
+        let source = "```metadata\nTitle T\nAuthor\n\tName Me\n\tSite x\nDate now\n```\nText";
+
+        assert_eq!(
+            assert_stable(source),
+            "```metadata\nTitle T\nAuthor\n    Name Me\n    Site x\nDate now\n```\n\nText\n"
+        );
+    }
+
+    #[test]
+    fn meta_is_stable() {
+        assert_stable(include_str!("../../test_source/meta.md"));
+    }
+
+    #[test]
+    fn definitions_in_first_use_order() {
+        let source = "[unused]: u\n[b]: b\n[a]: a\n\n{!a} {!Bee | b} {!a}";
+
+        assert_eq!(
+            assert_stable(source),
+            "{!a} {!Bee | b} {!a}\n\n[a]: a\n[b]: b\n[unused]: u\n"
+        );
+    }
+}
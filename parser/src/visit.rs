@@ -0,0 +1,437 @@
+//! Walking the whole tree of a [Parser], every block and every inline however
+//! deeply it's nested.
+//!
+//! Every method has a default that walks into the node's children, so
+//! implementors only override the nodes they care about. To still go into the
+//! children from an override, call the matching `walk_` function.
+
+use crate::{
+    element::{Alignment, Block, Inline, ListItem, Span, TableRow},
+    Parser,
+};
+
+pub trait Visitor {
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_header(&mut self, _level: u8, content: &[Inline], _span: Span) {
+        walk_inlines(self, content)
+    }
+
+    fn visit_paragraph(&mut self, content: &[Inline], _span: Span) {
+        walk_inlines(self, content)
+    }
+
+    fn visit_code_block(&mut self, _language: &str, _content: &str, _span: Span) {}
+
+    fn visit_image(&mut self, link: &Inline, _alt: &str, _span: Span) {
+        self.visit_inline(link)
+    }
+
+    fn visit_quote(&mut self, content: &[Block], _span: Span) {
+        walk_blocks(self, content)
+    }
+
+    fn visit_table(
+        &mut self,
+        header: Option<&TableRow>,
+        _alignments: &[Alignment],
+        rows: &[TableRow],
+        _span: Span,
+    ) {
+        for row in header.into_iter().chain(rows) {
+            self.visit_table_row(row);
+        }
+    }
+
+    fn visit_table_row(&mut self, row: &TableRow) {
+        for cell in &row.cells {
+            walk_inlines(self, cell);
+        }
+    }
+
+    fn visit_unordered_list(&mut self, items: &[ListItem], _span: Span) {
+        for item in items {
+            self.visit_list_item(item);
+        }
+    }
+
+    fn visit_ordered_list(&mut self, _start: usize, items: &[ListItem], _span: Span) {
+        for item in items {
+            self.visit_list_item(item);
+        }
+    }
+
+    fn visit_list_item(&mut self, item: &ListItem) {
+        walk_inlines(self, &item.content);
+        walk_blocks(self, &item.children);
+    }
+
+    fn visit_footnote(&mut self, _name: &str, content: &[Inline]) {
+        walk_inlines(self, content)
+    }
+
+    fn visit_inline(&mut self, inline: &Inline) {
+        walk_inline(self, inline)
+    }
+
+    fn visit_soft_break(&mut self, _span: Span) {}
+
+    fn visit_text(&mut self, _text: &str, _span: Span) {}
+
+    fn visit_code(&mut self, _code: &str, _span: Span) {}
+
+    fn visit_italic(&mut self, content: &[Inline], _span: Span) {
+        walk_inlines(self, content)
+    }
+
+    fn visit_bold(&mut self, content: &[Inline], _span: Span) {
+        walk_inlines(self, content)
+    }
+
+    fn visit_absolute_link(&mut self, _name: Option<&str>, _location: &str, _span: Span) {}
+
+    fn visit_reference_link(
+        &mut self,
+        _name: &str,
+        _reference: &str,
+        _location: &str,
+        _span: Span,
+    ) {
+    }
+
+    fn visit_interlink(&mut self, _name: &str, _location: &str, _span: Span) {}
+
+    fn visit_footnote_reference(&mut self, _name: &str, _span: Span) {}
+}
+
+/// Visits the blocks of the page in order, then every footnote sorted by name.
+pub fn walk_parser<V: Visitor + ?Sized>(visitor: &mut V, parser: &Parser) {
+    walk_blocks(visitor, &parser.blocks);
+
+    let mut names: Vec<&String> = parser.footnotes.keys().collect();
+    names.sort();
+    for name in names {
+        visitor.visit_footnote(name, &parser.footnotes[name]);
+    }
+}
+
+pub fn walk_blocks<V: Visitor + ?Sized>(visitor: &mut V, blocks: &[Block]) {
+    for block in blocks {
+        visitor.visit_block(block);
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+    match block {
+        Block::Header {
+            level,
+            content,
+            span,
+        } => visitor.visit_header(*level, content, *span),
+        Block::Paragraph { content, span } => visitor.visit_paragraph(content, *span),
+        Block::CodeBlock {
+            language,
+            content,
+            span,
+        } => visitor.visit_code_block(language, content, *span),
+        Block::Image { link, alt, span } => visitor.visit_image(link, alt, *span),
+        Block::Quote { content, span } => visitor.visit_quote(content, *span),
+        Block::Table {
+            header,
+            alignments,
+            rows,
+            span,
+        } => visitor.visit_table(header.as_ref(), alignments, rows, *span),
+        Block::UnorderedList { items, span } => visitor.visit_unordered_list(items, *span),
+        Block::OrderedList { start, items, span } => {
+            visitor.visit_ordered_list(*start, items, *span)
+        }
+    }
+}
+
+pub fn walk_inlines<V: Visitor + ?Sized>(visitor: &mut V, inlines: &[Inline]) {
+    for inline in inlines {
+        visitor.visit_inline(inline);
+    }
+}
+
+pub fn walk_inline<V: Visitor + ?Sized>(visitor: &mut V, inline: &Inline) {
+    match inline {
+        Inline::SoftBreak(span) => visitor.visit_soft_break(*span),
+        Inline::Text(text, span) => visitor.visit_text(text, *span),
+        Inline::Code(code, span) => visitor.visit_code(code, *span),
+        Inline::Italic { content, span } => visitor.visit_italic(content, *span),
+        Inline::Bold { content, span } => visitor.visit_bold(content, *span),
+        Inline::AbsoluteLink {
+            name,
+            location,
+            span,
+        } => visitor.visit_absolute_link(name.as_deref(), location, *span),
+        Inline::ReferenceLink {
+            name,
+            reference,
+            location,
+            span,
+        } => visitor.visit_reference_link(name, reference, location, *span),
+        Inline::InterLink {
+            name,
+            location,
+            span,
+        } => visitor.visit_interlink(name, location, *span),
+        Inline::FootnoteReference { name, span } => visitor.visit_footnote_reference(name, *span),
+    }
+}
+
+/// The same as [Visitor], but everything can be changed in place.
+pub trait VisitorMut {
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_header_mut(&mut self, _level: &mut u8, content: &mut Vec<Inline>, _span: &mut Span) {
+        walk_inlines_mut(self, content)
+    }
+
+    fn visit_paragraph_mut(&mut self, content: &mut Vec<Inline>, _span: &mut Span) {
+        walk_inlines_mut(self, content)
+    }
+
+    fn visit_code_block_mut(
+        &mut self,
+        _language: &mut String,
+        _content: &mut String,
+        _span: &mut Span,
+    ) {
+    }
+
+    fn visit_image_mut(&mut self, link: &mut Inline, _alt: &mut String, _span: &mut Span) {
+        self.visit_inline_mut(link)
+    }
+
+    fn visit_quote_mut(&mut self, content: &mut Vec<Block>, _span: &mut Span) {
+        walk_blocks_mut(self, content)
+    }
+
+    fn visit_table_mut(
+        &mut self,
+        header: Option<&mut TableRow>,
+        _alignments: &mut Vec<Alignment>,
+        rows: &mut Vec<TableRow>,
+        _span: &mut Span,
+    ) {
+        for row in header.into_iter().chain(rows) {
+            self.visit_table_row_mut(row);
+        }
+    }
+
+    fn visit_table_row_mut(&mut self, row: &mut TableRow) {
+        for cell in row.cells.iter_mut() {
+            walk_inlines_mut(self, cell);
+        }
+    }
+
+    fn visit_unordered_list_mut(&mut self, items: &mut Vec<ListItem>, _span: &mut Span) {
+        for item in items {
+            self.visit_list_item_mut(item);
+        }
+    }
+
+    fn visit_ordered_list_mut(
+        &mut self,
+        _start: &mut usize,
+        items: &mut Vec<ListItem>,
+        _span: &mut Span,
+    ) {
+        for item in items {
+            self.visit_list_item_mut(item);
+        }
+    }
+
+    fn visit_list_item_mut(&mut self, item: &mut ListItem) {
+        walk_inlines_mut(self, &mut item.content);
+        walk_blocks_mut(self, &mut item.children);
+    }
+
+    fn visit_footnote_mut(&mut self, _name: &str, content: &mut Vec<Inline>) {
+        walk_inlines_mut(self, content)
+    }
+
+    fn visit_inline_mut(&mut self, inline: &mut Inline) {
+        walk_inline_mut(self, inline)
+    }
+
+    fn visit_soft_break_mut(&mut self, _span: &mut Span) {}
+
+    fn visit_text_mut(&mut self, _text: &mut String, _span: &mut Span) {}
+
+    fn visit_code_mut(&mut self, _code: &mut String, _span: &mut Span) {}
+
+    fn visit_italic_mut(&mut self, content: &mut Vec<Inline>, _span: &mut Span) {
+        walk_inlines_mut(self, content)
+    }
+
+    fn visit_bold_mut(&mut self, content: &mut Vec<Inline>, _span: &mut Span) {
+        walk_inlines_mut(self, content)
+    }
+
+    fn visit_absolute_link_mut(
+        &mut self,
+        _name: &mut Option<String>,
+        _location: &mut String,
+        _span: &mut Span,
+    ) {
+    }
+
+    fn visit_reference_link_mut(
+        &mut self,
+        _name: &mut String,
+        _reference: &mut String,
+        _location: &mut String,
+        _span: &mut Span,
+    ) {
+    }
+
+    fn visit_interlink_mut(
+        &mut self,
+        _name: &mut String,
+        _location: &mut String,
+        _span: &mut Span,
+    ) {
+    }
+
+    fn visit_footnote_reference_mut(&mut self, _name: &mut String, _span: &mut Span) {}
+}
+
+/// Visits the blocks of the page in order, then every footnote sorted by name.
+pub fn walk_parser_mut<V: VisitorMut + ?Sized>(visitor: &mut V, parser: &mut Parser) {
+    walk_blocks_mut(visitor, &mut parser.blocks);
+
+    let mut footnotes: Vec<(&String, &mut Vec<Inline>)> = parser.footnotes.iter_mut().collect();
+    footnotes.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, content) in footnotes {
+        visitor.visit_footnote_mut(name, content);
+    }
+}
+
+pub fn walk_blocks_mut<V: VisitorMut + ?Sized>(visitor: &mut V, blocks: &mut [Block]) {
+    for block in blocks {
+        visitor.visit_block_mut(block);
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    match block {
+        Block::Header {
+            level,
+            content,
+            span,
+        } => visitor.visit_header_mut(level, content, span),
+        Block::Paragraph { content, span } => visitor.visit_paragraph_mut(content, span),
+        Block::CodeBlock {
+            language,
+            content,
+            span,
+        } => visitor.visit_code_block_mut(language, content, span),
+        Block::Image { link, alt, span } => visitor.visit_image_mut(link, alt, span),
+        Block::Quote { content, span } => visitor.visit_quote_mut(content, span),
+        Block::Table {
+            header,
+            alignments,
+            rows,
+            span,
+        } => visitor.visit_table_mut(header.as_mut(), alignments, rows, span),
+        Block::UnorderedList { items, span } => visitor.visit_unordered_list_mut(items, span),
+        Block::OrderedList { start, items, span } => {
+            visitor.visit_ordered_list_mut(start, items, span)
+        }
+    }
+}
+
+pub fn walk_inlines_mut<V: VisitorMut + ?Sized>(visitor: &mut V, inlines: &mut [Inline]) {
+    for inline in inlines {
+        visitor.visit_inline_mut(inline);
+    }
+}
+
+pub fn walk_inline_mut<V: VisitorMut + ?Sized>(visitor: &mut V, inline: &mut Inline) {
+    match inline {
+        Inline::SoftBreak(span) => visitor.visit_soft_break_mut(span),
+        Inline::Text(text, span) => visitor.visit_text_mut(text, span),
+        Inline::Code(code, span) => visitor.visit_code_mut(code, span),
+        Inline::Italic { content, span } => visitor.visit_italic_mut(content, span),
+        Inline::Bold { content, span } => visitor.visit_bold_mut(content, span),
+        Inline::AbsoluteLink {
+            name,
+            location,
+            span,
+        } => visitor.visit_absolute_link_mut(name, location, span),
+        Inline::ReferenceLink {
+            name,
+            reference,
+            location,
+            span,
+        } => visitor.visit_reference_link_mut(name, reference, location, span),
+        Inline::InterLink {
+            name,
+            location,
+            span,
+        } => visitor.visit_interlink_mut(name, location, span),
+        Inline::FootnoteReference { name, span } => {
+            visitor.visit_footnote_reference_mut(name, span)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Default)]
+    struct Interlinks(Vec<String>);
+
+    impl Visitor for Interlinks {
+        fn visit_interlink(&mut self, _name: &str, location: &str, _span: Span) {
+            self.0.push(location.to_owned());
+        }
+    }
+
+    struct Shout;
+
+    impl VisitorMut for Shout {
+        fn visit_text_mut(&mut self, text: &mut String, _span: &mut Span) {
+            *text = text.to_uppercase();
+        }
+    }
+
+    #[test]
+    fn visits_full_depth() {
+        let parser: Parser =
+            "*a **{one}***\n\n> - {two}\n>   - *{three}*\n\n| {four} |\n\nSee{^n}\n\n[^n]: {five}"
+                .parse()
+                .unwrap();
+
+        let mut links = Interlinks::default();
+        walk_parser(&mut links, &parser);
+
+        assert_eq!(links.0, vec!["one", "two", "three", "four", "five"]);
+    }
+
+    #[test]
+    fn changes_in_place() {
+        let mut parser: Parser = "# a *b*\n- **c**".parse().unwrap();
+        walk_parser_mut(&mut Shout, &mut parser);
+
+        let mut texts = vec![];
+        struct Texts<'a>(&'a mut Vec<String>);
+        impl<'a> Visitor for Texts<'a> {
+            fn visit_text(&mut self, text: &str, _span: Span) {
+                self.0.push(text.to_owned());
+            }
+        }
+        walk_parser(&mut Texts(&mut texts), &parser);
+
+        assert_eq!(texts, vec!["A ", "B", "C"]);
+    }
+}